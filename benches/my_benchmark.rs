@@ -2,7 +2,6 @@
 extern crate criterion;
 use std::collections::{BTreeMap, HashMap};
 
-use criterion::black_box;
 use criterion::{Criterion, ParameterizedBenchmark};
 use transit_rs::{de, ser};
 
@@ -67,5 +66,5 @@ fn deserialize_benchmark(c: &mut Criterion) {
     );
 }
 
-criterion_group!(benches, serialize_benchmark);
+criterion_group!(benches, serialize_benchmark, deserialize_benchmark);
 criterion_main!(benches);
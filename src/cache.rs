@@ -0,0 +1,138 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+const CACHE_CODE_DIGITS: usize = 44;
+const BASE_CHAR_INDEX: u8 = 48;
+const MAX_CACHE_ENTRIES: usize = CACHE_CODE_DIGITS * CACHE_CODE_DIGITS;
+
+/// Marker which opens a map written as an array in non-verbose modes
+pub(crate) const MAP_AS_ARRAY: &str = "^ ";
+
+/// Map keys, keywords, symbols and tags are cached if they are longer than
+/// three characters. Length is counted in UTF-16 code units to stay in sync
+/// with other Transit implementations.
+pub(crate) fn is_cacheable(s: &str, as_map_key: bool) -> bool {
+    s.len() > 3
+        && s.encode_utf16().nth(3).is_some()
+        && (as_map_key || s.starts_with("~#") || s.starts_with("~:") || s.starts_with("~$"))
+}
+
+fn is_cache_code(s: &str) -> bool {
+    s.starts_with('^') && s.len() > 1 && s != MAP_AS_ARRAY
+}
+
+fn index_to_code(i: usize) -> String {
+    let lo = (i % CACHE_CODE_DIGITS) as u8 + BASE_CHAR_INDEX;
+    let hi = i / CACHE_CODE_DIGITS;
+    if hi == 0 {
+        format!("^{}", lo as char)
+    } else {
+        format!("^{}{}", (hi as u8 + BASE_CHAR_INDEX) as char, lo as char)
+    }
+}
+
+fn code_to_index(s: &str) -> Option<usize> {
+    let digit = |c: u8| {
+        c.checked_sub(BASE_CHAR_INDEX)
+            .map(usize::from)
+            .filter(|d| *d < CACHE_CODE_DIGITS)
+    };
+    match s.as_bytes() {
+        [b'^', lo] => digit(*lo),
+        [b'^', hi, lo] => Some(digit(*hi)? * CACHE_CODE_DIGITS + digit(*lo)?),
+        _ => None,
+    }
+}
+
+/// Replaces repeating strings with cache codes (`^0`..`^ZZ`) during writing
+#[derive(Default)]
+pub(crate) struct WriteCache {
+    codes: HashMap<String, String>,
+}
+
+impl WriteCache {
+    pub(crate) fn cache_write(&mut self, s: String, as_map_key: bool) -> String {
+        if !is_cacheable(&s, as_map_key) {
+            return s;
+        }
+        if let Some(code) = self.codes.get(&s) {
+            return code.clone();
+        }
+        if self.codes.len() == MAX_CACHE_ENTRIES {
+            self.codes.clear();
+        }
+        let code = index_to_code(self.codes.len());
+        self.codes.insert(s.clone(), code);
+        s
+    }
+}
+
+/// Resolves cache codes back to strings during reading. Strings borrowed
+/// from input are kept borrowed.
+#[derive(Default)]
+pub(crate) struct ReadCache<'s> {
+    entries: Vec<Cow<'s, str>>,
+}
+
+impl<'s> ReadCache<'s> {
+    pub(crate) fn cache_read(&mut self, s: Cow<'s, str>, as_map_key: bool) -> Option<Cow<'s, str>> {
+        if is_cache_code(&s) {
+            return code_to_index(&s).and_then(|i| self.entries.get(i).cloned());
+        }
+        if is_cacheable(&s, as_map_key) {
+            if self.entries.len() == MAX_CACHE_ENTRIES {
+                self.entries.clear();
+            }
+            self.entries.push(s.clone());
+        }
+        Some(s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        assert_eq!("^0", index_to_code(0));
+        assert_eq!("^Z", index_to_code(42));
+        assert_eq!("^10", index_to_code(44));
+        assert_eq!("^ZZ", index_to_code(42 * CACHE_CODE_DIGITS + 42));
+        for i in &[0, 1, 43, 44, 45, 1000, MAX_CACHE_ENTRIES - 1] {
+            assert_eq!(Some(*i), code_to_index(&index_to_code(*i)));
+        }
+        assert_eq!(None, code_to_index("^ "));
+    }
+
+    #[test]
+    fn write_then_read() {
+        let mut w = WriteCache::default();
+        let mut r = ReadCache::default();
+        let input = [
+            ("~:keyword", false),
+            ("plain value", false),
+            ("key1", true),
+            ("~:keyword", false),
+            ("key1", true),
+            ("key", true),
+        ];
+        let written: Vec<String> = input
+            .iter()
+            .map(|(s, k)| w.cache_write(s.to_string(), *k))
+            .collect();
+        assert_eq!(
+            vec!["~:keyword", "plain value", "key1", "^0", "^1", "key"],
+            written
+        );
+        let read: Vec<String> = written
+            .into_iter()
+            .zip(input.iter())
+            .map(|(s, (_, k))| r.cache_read(s.into(), *k).unwrap().into_owned())
+            .collect();
+        assert_eq!(
+            input.iter().map(|(s, _)| s.to_string()).collect::<Vec<_>>(),
+            read
+        );
+    }
+}
@@ -1,22 +1,67 @@
+pub mod edn;
+pub mod handlers;
 mod impls;
+pub mod json;
 pub mod json_verbose;
+pub mod msgpack;
+mod serde_bridge;
+pub mod stream;
 
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::{BTreeMap, HashMap};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::default::Default;
 use std::fmt::Debug;
-use std::mem;
 
 #[derive(Debug)]
 pub enum Error {
     DoNotMatch(String),
     ItWontFit(String),
     CannotBeKey(&'static str),
+    CannotParse(String),
+    Io(std::io::Error),
+    /// Error raised by serde implementations
+    Custom(String),
 }
 
-type TResult<T> = Result<T, Error>;
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::DoNotMatch(s)
+            | Error::ItWontFit(s)
+            | Error::CannotParse(s)
+            | Error::Custom(s) => f.write_str(s),
+            Error::CannotBeKey(s) => f.write_str(s),
+            Error::Io(e) => std::fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub(crate) type TResult<T> = Result<T, Error>;
+
+/// Reads `~z` encoding of NaN and infinities
+pub(crate) fn special_float(s: &str) -> Option<f64> {
+    match s {
+        "~zNaN" => Some(f64::NAN),
+        "~zINF" => Some(f64::INFINITY),
+        "~z-INF" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+pub(crate) fn bytes_from_str(s: &str) -> TResult<Vec<u8>> {
+    let b64 = s
+        .strip_prefix("~b")
+        .ok_or_else(|| Error::DoNotMatch(format!("{:?} is not bytes", s)))?;
+    BASE64
+        .decode(b64)
+        .map_err(|e| Error::CannotParse(format!("{:?} is not base64: {}", b64, e)))
+}
 
 #[derive(PartialEq)]
 pub enum TransitType {
@@ -24,30 +69,64 @@ pub enum TransitType {
     Composite,
 }
 
-pub trait TransitDeserialize: Sized {
+/// What an input holds, so self-describing types like `TransitValue` know
+/// which `deserialize_*` method to call. Tagged values are maps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputKind {
+    Null,
+    String,
+    Bool,
+    Int,
+    Float,
+    Array,
+    Map,
+    /// Native binary, only MessagePack has it
+    Bytes,
+}
+
+/// `'de` is lifetime of input, which values like `&'de str` may borrow from
+pub trait TransitDeserialize<'de>: Sized {
     const TF_TYPE: TransitType;
 
-    fn transit_deserialize<D: TransitDeserializer>(
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self>;
 
-    fn transit_deserialize_key<D: TransitDeserializer>(
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self>;
 }
 
-pub trait TransitDeserializer: Debug + Clone {
-    type Input: Debug + Default;
+/// Values which do not borrow anything, they can be read from any input
+pub trait TransitDeserializeOwned: for<'de> TransitDeserialize<'de> {}
+
+impl<T> TransitDeserializeOwned for T where T: for<'de> TransitDeserialize<'de> {}
+
+pub trait TransitDeserializer<'de>: Debug + Clone {
+    type Input: Debug;
     type DeserializeArray: Iterator<Item = Self::Input>;
     type DeserializeMap: Iterator<Item = (Self::Input, Self::Input)>;
 
     //fn deserialize_null(self, v: Self::Input) -> Self::Output;
     fn deserialize_string(self, v: Self::Input) -> TResult<String>;
+    /// Borrows the string from input if the deserializer is able to
+    fn deserialize_str(self, v: Self::Input) -> TResult<Cow<'de, str>> {
+        self.deserialize_string(v).map(Cow::Owned)
+    }
     fn deserialize_bool(self, v: Self::Input) -> TResult<bool>;
     fn deserialize_int(self, v: Self::Input) -> TResult<i64>;
     fn deserialize_float(self, v: Self::Input) -> TResult<f64>;
+    /// Reads `~b` base64 strings unless the format has native binary
+    fn deserialize_bytes(self, v: Self::Input) -> TResult<Vec<u8>> {
+        bytes_from_str(&self.deserialize_string(v)?)
+    }
     fn deserialize_array(self, v: Self::Input) -> TResult<(Self::DeserializeArray, Option<usize>)>;
     fn deserialize_map(self, v: Self::Input) -> TResult<(Self::DeserializeMap, Option<usize>)>;
+    fn input_kind(&self, v: &Self::Input) -> TResult<InputKind>;
+    /// Handlers of custom tags registered for this read
+    fn read_handlers(&self) -> Option<&handlers::ReadHandlers> {
+        None
+    }
 }
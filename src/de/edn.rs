@@ -0,0 +1,575 @@
+use super::*;
+
+/// Reads a single EDN value, e.g. a fixture or config file. EDN is
+/// translated into the Transit type model: keywords become `~:` strings,
+/// `#inst` becomes `~t`, sets become `~#set` and so on.
+pub fn from_str<T: TransitDeserializeOwned>(s: &str) -> TResult<T> {
+    let mut parser = Parser {
+        input: s,
+        pos: 0,
+        depth: 0,
+    };
+    let v = parser.parse_value()?;
+    parser.skip_ws()?;
+    if parser.pos < s.len() {
+        return Err(parser.error("trailing characters after value"));
+    }
+    TransitDeserialize::transit_deserialize(EdnDeserializer, v)
+}
+
+/// EDN value where scalars are already encoded as Transit strings and maps
+/// with composite keys are `~#cmap` tagged vectors
+#[derive(Clone, Debug, PartialEq)]
+enum EdnVal {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Vector(Vec<EdnVal>),
+    Map(Vec<(EdnVal, EdnVal)>),
+    Tagged(String, Box<EdnVal>),
+}
+
+impl EdnVal {
+    /// Scalars as they are written in map keys
+    fn as_key(&self) -> Option<String> {
+        let k = match self {
+            EdnVal::Null => "~_".to_owned(),
+            EdnVal::Bool(true) => "~?t".to_owned(),
+            EdnVal::Bool(false) => "~?f".to_owned(),
+            EdnVal::Int(i) => format!("~i{}", i),
+            EdnVal::Float(f) if f.is_nan() => "~zNaN".to_owned(),
+            EdnVal::Float(f) if f.is_infinite() => {
+                if *f > 0.0 { "~zINF" } else { "~z-INF" }.to_owned()
+            }
+            EdnVal::Float(f) => format!("~d{}", f),
+            EdnVal::String(s) => s.clone(),
+            _ => return None,
+        };
+        Some(k)
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ',' | ';' | '"' | '(' | ')' | '[' | ']' | '{' | '}')
+}
+
+/// Optional sign followed by decimal digits
+fn is_integer(s: &str) -> bool {
+    let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn escape(s: String) -> String {
+    if s.starts_with('~') || s.starts_with('^') || s.starts_with('`') {
+        format!("~{}", s)
+    } else {
+        s
+    }
+}
+
+/// Forms nested deeper than this are rejected instead of exhausting the
+/// stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> Error {
+        Error::CannotParse(format!("{} at position {}", msg, self.pos))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Skips whitespace, commas, comments and `#_` discarded forms
+    fn skip_ws(&mut self) -> TResult<()> {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ',' {
+                self.bump();
+            } else if c == ';' {
+                while !matches!(self.bump(), Some('\n') | None) {}
+            } else if self.input[self.pos..].starts_with("#_") {
+                self.pos += 2;
+                self.parse_value()?;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn token(&mut self) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !is_delimiter(c)) {
+            self.bump();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn parse_value(&mut self) -> TResult<EdnVal> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+        self.depth += 1;
+        let v = self.parse_form();
+        self.depth -= 1;
+        v
+    }
+
+    fn parse_form(&mut self) -> TResult<EdnVal> {
+        self.skip_ws()?;
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("unexpected end of input"))?;
+        match c {
+            '"' => {
+                self.bump();
+                self.parse_string().map(|s| EdnVal::String(escape(s)))
+            }
+            '[' => {
+                self.bump();
+                self.parse_seq(']').map(EdnVal::Vector)
+            }
+            '(' => {
+                self.bump();
+                let items = self.parse_seq(')')?;
+                Ok(EdnVal::Tagged(
+                    "~#list".to_owned(),
+                    Box::new(EdnVal::Vector(items)),
+                ))
+            }
+            '{' => {
+                self.bump();
+                self.parse_map()
+            }
+            '\\' => {
+                self.bump();
+                self.parse_char()
+                    .map(|c| EdnVal::String(format!("~c{}", c)))
+            }
+            ':' => {
+                self.bump();
+                let name = self.token();
+                if name.is_empty() {
+                    return Err(self.error("empty keyword"));
+                }
+                Ok(EdnVal::String(format!("~:{}", name)))
+            }
+            '#' => {
+                self.bump();
+                self.parse_dispatch()
+            }
+            ')' | ']' | '}' => Err(self.error("unmatched delimiter")),
+            _ => self.parse_atom(),
+        }
+    }
+
+    fn parse_string(&mut self) -> TResult<String> {
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('u') => s.push(self.parse_unicode()?),
+                    _ => return Err(self.error("invalid escape in string")),
+                },
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_unicode(&mut self) -> TResult<char> {
+        let hex = self.input.get(self.pos..self.pos + 4).unwrap_or("");
+        let c = u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(c)
+    }
+
+    fn parse_char(&mut self) -> TResult<char> {
+        let first = self
+            .bump()
+            .ok_or_else(|| self.error("unexpected end of input"))?;
+        let rest = self.token();
+        let c = match (first, rest) {
+            (c, "") => c,
+            ('u', hex) if hex.len() == 4 => {
+                self.pos -= 4;
+                return self.parse_unicode();
+            }
+            _ => match [first.to_string(), rest.to_owned()].concat().as_str() {
+                "newline" => '\n',
+                "space" => ' ',
+                "tab" => '\t',
+                "return" => '\r',
+                "backspace" => '\u{8}',
+                "formfeed" => '\u{c}',
+                _ => return Err(self.error("unknown character name")),
+            },
+        };
+        Ok(c)
+    }
+
+    fn parse_seq(&mut self, close: char) -> TResult<Vec<EdnVal>> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws()?;
+            match self.peek() {
+                Some(c) if c == close => {
+                    self.bump();
+                    return Ok(items);
+                }
+                Some(_) => items.push(self.parse_value()?),
+                None => return Err(self.error("unterminated collection")),
+            }
+        }
+    }
+
+    fn parse_map(&mut self) -> TResult<EdnVal> {
+        let items = self.parse_seq('}')?;
+        if items.len() % 2 == 1 {
+            return Err(self.error("map must have even number of forms"));
+        }
+        let mut keys = Vec::with_capacity(items.len() / 2);
+        let mut vals = Vec::with_capacity(items.len() / 2);
+        let mut items = items.into_iter();
+        while let (Some(k), Some(v)) = (items.next(), items.next()) {
+            keys.push(k);
+            vals.push(v);
+        }
+        if let Some(keys) = keys.iter().map(EdnVal::as_key).collect::<Option<Vec<_>>>() {
+            let pairs = keys.into_iter().map(EdnVal::String).zip(vals).collect();
+            Ok(EdnVal::Map(pairs))
+        } else {
+            let interleaved = keys.into_iter().zip(vals).flat_map(|(k, v)| vec![k, v]);
+            Ok(EdnVal::Tagged(
+                "~#cmap".to_owned(),
+                Box::new(EdnVal::Vector(interleaved.collect())),
+            ))
+        }
+    }
+
+    /// Forms starting with `#`: sets, symbolic values and tagged literals
+    fn parse_dispatch(&mut self) -> TResult<EdnVal> {
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                let items = self.parse_seq('}')?;
+                return Ok(EdnVal::Tagged(
+                    "~#set".to_owned(),
+                    Box::new(EdnVal::Vector(items)),
+                ));
+            }
+            Some('#') => {
+                self.bump();
+                return match self.token() {
+                    "NaN" => Ok(EdnVal::Float(f64::NAN)),
+                    "Inf" => Ok(EdnVal::Float(f64::INFINITY)),
+                    "-Inf" => Ok(EdnVal::Float(f64::NEG_INFINITY)),
+                    _ => Err(self.error("unknown symbolic value")),
+                };
+            }
+            _ => (),
+        }
+        let tag = self.token().to_owned();
+        if !tag.starts_with(|c: char| c.is_alphabetic()) {
+            return Err(self.error("tag must start with a letter"));
+        }
+        let rep = self.parse_value()?;
+        let prefix = match tag.as_str() {
+            "inst" => "~t".to_owned(),
+            "uuid" => "~u".to_owned(),
+            "uri" => "~r".to_owned(),
+            "base64" => "~b".to_owned(),
+            // Transit scalar tags, e.g. `~xrep`
+            tag if tag.chars().count() == 1 => format!("~{}", tag),
+            _ => return Ok(EdnVal::Tagged(format!("~#{}", tag), Box::new(rep))),
+        };
+        match rep {
+            // Keywords, symbols and characters are encoded strings too, but
+            // only escaped ones come from string literals
+            EdnVal::String(s) if !s.starts_with('~') => Ok(EdnVal::String(prefix + &s)),
+            EdnVal::String(s) if s[1..].starts_with(['~', '^', '`']) => {
+                Ok(EdnVal::String(prefix + &s[1..]))
+            }
+            _ => Err(self.error("tagged literal must have string form")),
+        }
+    }
+
+    fn parse_atom(&mut self) -> TResult<EdnVal> {
+        let token = self.token().to_owned();
+        if token.is_empty() {
+            return Err(self.error("unexpected character"));
+        }
+        let numeric = token.starts_with(|c: char| c.is_ascii_digit())
+            || (token.len() > 1
+                && token.starts_with(['+', '-'])
+                && token[1..].starts_with(|c: char| c.is_ascii_digit()));
+        if !numeric {
+            return Ok(match token.as_str() {
+                "nil" => EdnVal::Null,
+                "true" => EdnVal::Bool(true),
+                "false" => EdnVal::Bool(false),
+                _ => EdnVal::String(format!("~${}", token)),
+            });
+        }
+        let digits = token.strip_prefix('+').unwrap_or(&token);
+        let invalid = || self.error("invalid number");
+        if let Some(n) = digits.strip_suffix('N') {
+            if !is_integer(n) {
+                return Err(invalid());
+            }
+            Ok(EdnVal::String(format!("~n{}", n)))
+        } else if let Some(d) = digits.strip_suffix('M') {
+            d.parse::<f64>().map_err(|_| invalid())?;
+            Ok(EdnVal::String(format!("~f{}", d)))
+        } else if digits.contains(['.', 'e', 'E']) {
+            digits.parse().map(EdnVal::Float).map_err(|_| invalid())
+        } else if let Ok(i) = digits.parse() {
+            Ok(EdnVal::Int(i))
+        } else if is_integer(digits) {
+            Ok(EdnVal::String(format!("~n{}", digits)))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct EdnDeserializer;
+
+impl<'de> TransitDeserializer<'de> for EdnDeserializer {
+    type Input = EdnVal;
+    type DeserializeArray = std::vec::IntoIter<EdnVal>;
+    type DeserializeMap = std::vec::IntoIter<(EdnVal, EdnVal)>;
+
+    fn deserialize_string(self, v: Self::Input) -> TResult<String> {
+        if let EdnVal::String(s) = v {
+            Ok(s)
+        } else {
+            Err(Error::DoNotMatch(format!("{:?} is not string", v)))
+        }
+    }
+
+    fn deserialize_bool(self, v: Self::Input) -> TResult<bool> {
+        if let EdnVal::Bool(b) = v {
+            Ok(b)
+        } else {
+            Err(Error::DoNotMatch(format!("{:?} is not bool", v)))
+        }
+    }
+
+    fn deserialize_int(self, v: Self::Input) -> TResult<i64> {
+        if let EdnVal::Int(i) = v {
+            Ok(i)
+        } else {
+            Err(Error::DoNotMatch(format!("{:?} is not int", v)))
+        }
+    }
+
+    fn deserialize_float(self, v: Self::Input) -> TResult<f64> {
+        match v {
+            EdnVal::Float(f) => Ok(f),
+            EdnVal::Int(i) => Ok(i as f64),
+            v => Err(Error::DoNotMatch(format!("{:?} is not float", v))),
+        }
+    }
+
+    fn deserialize_array(self, v: Self::Input) -> TResult<(Self::DeserializeArray, Option<usize>)> {
+        if let EdnVal::Vector(vec) = v {
+            let l = vec.len();
+            Ok((vec.into_iter(), Some(l)))
+        } else {
+            Err(Error::DoNotMatch(format!("{:?} is not an array", v)))
+        }
+    }
+
+    fn deserialize_map(self, v: Self::Input) -> TResult<(Self::DeserializeMap, Option<usize>)> {
+        let pairs = match v {
+            EdnVal::Map(pairs) => pairs,
+            EdnVal::Tagged(tag, rep) => vec![(EdnVal::String(tag), *rep)],
+            v => return Err(Error::DoNotMatch(format!("{:?} is not a map", v))),
+        };
+        let l = pairs.len();
+        Ok((pairs.into_iter(), Some(l)))
+    }
+
+    fn input_kind(&self, v: &Self::Input) -> TResult<InputKind> {
+        Ok(match v {
+            EdnVal::Null => InputKind::Null,
+            EdnVal::Bool(_) => InputKind::Bool,
+            EdnVal::Int(_) => InputKind::Int,
+            EdnVal::Float(_) => InputKind::Float,
+            EdnVal::String(_) => InputKind::String,
+            EdnVal::Vector(_) => InputKind::Array,
+            EdnVal::Map(_) | EdnVal::Tagged(..) => InputKind::Map,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ser::edn::to_string;
+    use crate::value::TransitValue;
+    use chrono::{TimeZone, Utc};
+
+    fn kw(s: &str) -> TransitValue {
+        TransitValue::Keyword(s.to_owned())
+    }
+
+    #[test]
+    fn scalars() {
+        let tr: Vec<TransitValue> =
+            from_str(r#"[nil true -4 +2.5e1 12N 1.5M ##-Inf "~str\"é" \a \newline :ns/kw sym]"#)
+                .unwrap();
+        assert_eq!(
+            vec![
+                TransitValue::Null,
+                TransitValue::Bool(true),
+                TransitValue::Int(-4),
+                TransitValue::Float(25.0),
+                TransitValue::BigInt("12".to_owned()),
+                TransitValue::BigDec("1.5".to_owned()),
+                TransitValue::Float(f64::NEG_INFINITY),
+                TransitValue::String("~str\"é".to_owned()),
+                TransitValue::Char('a'),
+                TransitValue::Char('\n'),
+                kw("ns/kw"),
+                TransitValue::Symbol("sym".to_owned()),
+            ],
+            tr
+        );
+    }
+
+    #[test]
+    fn collections() {
+        let tr: TransitValue = from_str(
+            r#"
+            ; fixture
+            {:set #{1 2}, :list (1 #_ 2), [1] {nil "x"}
+             :inst #inst "2019-04-01T12:30:00.000Z"
+             :uuid #uuid "5a2cbb1f-d1f2-4c6e-8f5c-19ae24c1a7e0"
+             :tag #my/tag {:a 1}}
+            "#,
+        )
+        .unwrap();
+        let expected = TransitValue::Map(vec![
+            (
+                kw("set"),
+                TransitValue::Set(vec![TransitValue::Int(1), TransitValue::Int(2)]),
+            ),
+            (kw("list"), TransitValue::List(vec![TransitValue::Int(1)])),
+            (
+                TransitValue::Array(vec![TransitValue::Int(1)]),
+                TransitValue::Map(vec![(
+                    TransitValue::Null,
+                    TransitValue::String("x".to_owned()),
+                )]),
+            ),
+            (
+                kw("inst"),
+                TransitValue::Instant(Utc.with_ymd_and_hms(2019, 4, 1, 12, 30, 0).unwrap()),
+            ),
+            (
+                kw("uuid"),
+                TransitValue::Uuid(0x5a2c_bb1f_d1f2_4c6e_8f5c_19ae_24c1_a7e0),
+            ),
+            (
+                kw("tag"),
+                TransitValue::Tagged(
+                    "my/tag".to_owned(),
+                    Box::new(TransitValue::Map(vec![(kw("a"), TransitValue::Int(1))])),
+                ),
+            ),
+        ]);
+        assert_eq!(expected, tr);
+    }
+
+    #[test]
+    fn typed() {
+        let tr: BTreeMap<i32, BTreeSet<String>> = from_str(r#"{1 #{"a" "b"}, 2 #{}}"#).unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(
+            1,
+            vec!["a".to_owned(), "b".to_owned()].into_iter().collect(),
+        );
+        m.insert(2, BTreeSet::new());
+        assert_eq!(m, tr);
+        assert_eq!(m, from_str(&to_string(&m)).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        for s in &["[1 2", "{:a}", "(1))", "#inst 5", "\\unknown", "#{1} 2", ""] {
+            assert!(from_str::<TransitValue>(s).is_err(), "{} must fail", s);
+        }
+        for s in &["1-2N", "1aN", "+1.5N"] {
+            assert!(from_str::<TransitValue>(s).is_err(), "{} must fail", s);
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(from_str::<TransitValue>(&nested(100)).is_ok());
+        for s in &[
+            nested(100_000),
+            "#_".repeat(100_000) + "1",
+            "#{".repeat(100_000),
+        ] {
+            match from_str::<TransitValue>(s) {
+                Err(Error::CannotParse(_)) => (),
+                tr => panic!("{:?}", tr.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let v = TransitValue::Array(vec![
+            TransitValue::Bytes(vec![1, 2, 3]),
+            TransitValue::Uri("https://example.com".to_owned()),
+            TransitValue::Char(' '),
+            TransitValue::Float(f64::INFINITY),
+            TransitValue::Tagged(
+                "x".to_owned(),
+                Box::new(TransitValue::String("~y".to_owned())),
+            ),
+            TransitValue::Map(vec![(
+                TransitValue::Set(vec![TransitValue::Float(1.5)]),
+                TransitValue::List(vec![]),
+            )]),
+        ]);
+        assert_eq!(v, from_str(&to_string(&v)).unwrap());
+        let big = TransitValue::BigInt("-12345678901234567890123456789012345678901234".to_owned());
+        assert_eq!(big, from_str(&to_string(&big)).unwrap());
+        let pretty = crate::ser::edn::PrettyPrinter::new()
+            .width(10)
+            .to_string(&v);
+        assert_eq!(v, from_str(&pretty).unwrap());
+    }
+}
@@ -0,0 +1,260 @@
+//! Runtime registry of read handlers, which turn tagged values written by
+//! other Transit implementations into user types.
+
+use super::*;
+use crate::value::{TaggedValue, TransitValue};
+use std::any::{Any, TypeId};
+use std::fmt;
+
+/// Decodes representation of a value with a known tag into `T`
+pub trait ReadHandler<T> {
+    fn read(&self, rep: TransitValue) -> Result<T, Error>;
+}
+
+impl<T, F: Fn(TransitValue) -> Result<T, Error>> ReadHandler<T> for F {
+    fn read(&self, rep: TransitValue) -> Result<T, Error> {
+        self(rep)
+    }
+}
+
+/// Decodes values with tags which have no handler of their own
+pub trait DefaultReadHandler<T> {
+    fn read(&self, tag: &str, rep: TransitValue) -> Result<T, Error>;
+}
+
+impl<T, F: Fn(&str, TransitValue) -> Result<T, Error>> DefaultReadHandler<T> for F {
+    fn read(&self, tag: &str, rep: TransitValue) -> Result<T, Error> {
+        self(tag, rep)
+    }
+}
+
+/// Handlers keyed by the tag they read and the type they produce. Tags are
+/// registered without prefix, so `"x"` reads both `"~xrep"` and
+/// `["~#x", rep]`.
+#[derive(Default)]
+pub struct ReadHandlers {
+    handlers: HashMap<(TypeId, String), Box<dyn Any>>,
+    defaults: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl fmt::Debug for ReadHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ReadHandlers({} handlers, {} defaults)",
+            self.handlers.len(),
+            self.defaults.len()
+        )
+    }
+}
+
+impl ReadHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a handler of `tag` for `T`, replacing the previous one
+    pub fn insert<T: 'static, H: ReadHandler<T> + 'static>(
+        &mut self,
+        tag: &str,
+        handler: H,
+    ) -> &mut Self {
+        let handler: Box<dyn ReadHandler<T>> = Box::new(handler);
+        self.handlers
+            .insert((TypeId::of::<T>(), tag.to_owned()), Box::new(handler));
+        self
+    }
+
+    /// Registers a handler of all unknown tags for `T`
+    pub fn set_default<T: 'static, H: DefaultReadHandler<T> + 'static>(
+        &mut self,
+        handler: H,
+    ) -> &mut Self {
+        let handler: Box<dyn DefaultReadHandler<T>> = Box::new(handler);
+        self.defaults.insert(TypeId::of::<T>(), Box::new(handler));
+        self
+    }
+
+    /// Decodes `rep` with handler of `tag`, or with the default handler
+    pub fn decode<T: 'static>(&self, tag: &str, rep: TransitValue) -> Result<T, Error> {
+        let handler = self
+            .handlers
+            .get(&(TypeId::of::<T>(), tag.to_owned()))
+            .and_then(|h| h.downcast_ref::<Box<dyn ReadHandler<T>>>());
+        if let Some(h) = handler {
+            return h.read(rep);
+        }
+        let default = self
+            .defaults
+            .get(&TypeId::of::<T>())
+            .and_then(|h| h.downcast_ref::<Box<dyn DefaultReadHandler<T>>>());
+        match default {
+            Some(h) => h.read(tag, rep),
+            None => Err(Error::DoNotMatch(format!(
+                "No read handler of {:?} for {} is registered",
+                tag,
+                std::any::type_name::<T>()
+            ))),
+        }
+    }
+}
+
+fn handlers<'d, 'de, D: TransitDeserializer<'de>>(
+    deserializer: &'d D,
+) -> TResult<&'d ReadHandlers> {
+    deserializer
+        .read_handlers()
+        .ok_or_else(|| Error::DoNotMatch("Reader has no read handlers".to_owned()))
+}
+
+/// Wrapper for values which are read only with a registered handler. Scalar
+/// tags may be map keys, so maps of `Handled` keys read them one by one.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handled<T>(pub T);
+
+impl<'de, T: 'static> TransitDeserialize<'de> for Handled<T> {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let handlers = handlers(&deserializer)?;
+        let v = TaggedValue::transit_deserialize(deserializer.clone(), input)?;
+        let tag = v.tag.clone();
+        handlers.decode(&tag, v.decode_rep()?).map(Handled)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let handlers = handlers(&deserializer)?;
+        let v = TaggedValue::transit_deserialize_key(deserializer.clone(), input)?;
+        let tag = v.tag.clone();
+        handlers.decode(&tag, v.decode_rep()?).map(Handled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::de::{json, json_verbose, msgpack, stream};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Ext {
+        Point(i64, i64),
+        Color(String),
+        Unknown(String),
+    }
+
+    fn handlers() -> ReadHandlers {
+        let mut h = ReadHandlers::new();
+        h.insert("point", |rep: TransitValue| match rep {
+            TransitValue::Array(v) => match v.as_slice() {
+                [TransitValue::Int(x), TransitValue::Int(y)] => Ok(Ext::Point(*x, *y)),
+                _ => Err(Error::DoNotMatch("point must be a pair".to_owned())),
+            },
+            _ => Err(Error::DoNotMatch("point must be an array".to_owned())),
+        })
+        .insert("x", |rep: TransitValue| match rep {
+            TransitValue::String(s) => Ok(Ext::Color(s)),
+            _ => Err(Error::DoNotMatch("color must be a string".to_owned())),
+        });
+        h
+    }
+
+    #[test]
+    fn tags() {
+        let h = handlers();
+        let expected = vec![
+            Handled(Ext::Point(1, 2)),
+            Handled(Ext::Color("red".to_owned())),
+        ];
+
+        let tr: Vec<Handled<Ext>> =
+            json::from_transit_json_with(json!([["~#point", [1, 2]], "~xred"]), &h).unwrap();
+        assert_eq!(expected, tr);
+
+        let tr: Vec<Handled<Ext>> =
+            json_verbose::from_transit_json_with(json!([{"~#point": [1, 2]}, "~xred"]), &h)
+                .unwrap();
+        assert_eq!(expected, tr);
+
+        let tr: Vec<Handled<Ext>> =
+            stream::from_slice_with(br#"[["~#point",[1,2]],"~xred"]"#, &h).unwrap();
+        assert_eq!(expected, tr);
+
+        let v = TransitValue::Array(vec![
+            TransitValue::Tagged(
+                "point".to_owned(),
+                Box::new(TransitValue::Array(vec![
+                    TransitValue::Int(1),
+                    TransitValue::Int(2),
+                ])),
+            ),
+            TransitValue::Tagged(
+                "x".to_owned(),
+                Box::new(TransitValue::String("red".to_owned())),
+            ),
+        ]);
+        let mp = crate::ser::msgpack::to_transit_msgpack(v);
+        let tr: Vec<Handled<Ext>> = msgpack::from_transit_msgpack_with(&mp, &h).unwrap();
+        assert_eq!(expected, tr);
+    }
+
+    #[test]
+    fn stream_sequence() {
+        let input = "[\"~#point\",[1,2]]\n\"~xred\"\n";
+        let tr: Vec<Handled<Ext>> =
+            stream::StreamDeserializer::with_handlers(input.as_bytes(), handlers())
+                .collect::<TResult<_>>()
+                .unwrap();
+        assert_eq!(
+            vec![
+                Handled(Ext::Point(1, 2)),
+                Handled(Ext::Color("red".to_owned()))
+            ],
+            tr
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let h = handlers();
+        let tr: BTreeMap<Handled<Ext>, i32> =
+            json::from_transit_json_with(json!(["^ ", "~xred", 1]), &h).unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(Handled(Ext::Color("red".to_owned())), 1);
+        assert_eq!(m, tr);
+    }
+
+    #[test]
+    fn default_handler() {
+        let mut h = handlers();
+        h.set_default(|tag: &str, _rep: TransitValue| Ok(Ext::Unknown(tag.to_owned())));
+        let tr: Vec<Handled<Ext>> =
+            json::from_transit_json_with(json!([["~#line", [1, 2]], "~yz"]), &h).unwrap();
+        assert_eq!(
+            vec![
+                Handled(Ext::Unknown("line".to_owned())),
+                Handled(Ext::Unknown("y".to_owned()))
+            ],
+            tr
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let h = handlers();
+        let tr: TResult<Vec<Handled<Ext>>> =
+            json::from_transit_json_with(json!([["~#line", [1, 2]]]), &h);
+        assert!(tr.is_err());
+        let tr: TResult<Vec<Handled<Ext>>> = json::from_transit_json_with(json!(["red"]), &h);
+        assert!(tr.is_err());
+        let tr: TResult<Vec<Handled<Ext>>> = json::from_transit_json(json!(["~xred"]));
+        assert!(tr.is_err());
+    }
+}
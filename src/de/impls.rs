@@ -1,13 +1,59 @@
 use super::*;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use std::time::SystemTime;
 
-impl<K, V> TransitDeserialize for BTreeMap<K, V>
+/// Reverts escaping of strings which start with `~`, `^` or `` ` ``,
+/// borrowed strings stay borrowed
+fn unescape(s: Cow<'_, str>) -> Cow<'_, str> {
+    if !(s.starts_with("~~") || s.starts_with("~^") || s.starts_with("~`")) {
+        return s;
+    }
+    match s {
+        Cow::Borrowed(b) => Cow::Borrowed(&b[1..]),
+        Cow::Owned(mut o) => {
+            o.remove(0);
+            Cow::Owned(o)
+        }
+    }
+}
+
+fn borrowed(s: Cow<'_, str>) -> TResult<&str> {
+    match s {
+        Cow::Borrowed(b) => Ok(b),
+        Cow::Owned(o) => Err(Error::DoNotMatch(format!(
+            "{:?} cannot be borrowed from input",
+            o
+        ))),
+    }
+}
+
+/// Takes representation of a tagged value, which is seen as a map with
+/// a single entry
+fn deserialize_tagged<'de, D: TransitDeserializer<'de>>(
+    deserializer: D,
+    input: D::Input,
+    tag: &str,
+) -> TResult<D::Input> {
+    let (mut map_iter, _) = deserializer.clone().deserialize_map(input)?;
+    let (k, v) = map_iter
+        .next()
+        .ok_or_else(|| Error::DoNotMatch(format!("empty map instead of {}", tag)))?;
+    let k_str = deserializer.deserialize_string(k)?;
+    if k_str == tag {
+        Ok(v)
+    } else {
+        Err(Error::DoNotMatch(format!("{:?} must be {}", k_str, tag)))
+    }
+}
+
+impl<'de, K, V> TransitDeserialize<'de> for BTreeMap<K, V>
 where
-    K: TransitDeserialize + Ord,
-    V: TransitDeserialize,
+    K: TransitDeserialize<'de> + Ord,
+    V: TransitDeserialize<'de>,
 {
     const TF_TYPE: TransitType = TransitType::Composite;
 
-    fn transit_deserialize<D: TransitDeserializer>(
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
@@ -31,18 +77,15 @@ where
                     } else {
                         Err(Error::DoNotMatch(format!("{:?} must be ~#cmap", k_str)))
                     }?;
-                    let mut vals: Vec<D::Input> =
-                        deserializer.clone().deserialize_array(v)?.0.collect();
-                    for i in (0..vals.len()).step_by(2) {
+                    let mut vals = deserializer.clone().deserialize_array(v)?.0;
+                    while let Some(k) = vals.next() {
+                        let k = TransitDeserialize::transit_deserialize(deserializer.clone(), k)?;
+                        let v = vals.next().ok_or_else(|| {
+                            Error::DoNotMatch("~#cmap must have even number of items".to_owned())
+                        })?;
                         result.insert(
-                            TransitDeserialize::transit_deserialize(
-                                deserializer.clone(),
-                                mem::replace(&mut vals[i], D::Input::default()),
-                            )?,
-                            TransitDeserialize::transit_deserialize(
-                                deserializer.clone(),
-                                mem::replace(&mut vals[i + 1], D::Input::default()),
-                            )?,
+                            k,
+                            TransitDeserialize::transit_deserialize(deserializer.clone(), v)?,
                         );
                     }
                 }
@@ -51,7 +94,7 @@ where
         }
     }
 
-    fn transit_deserialize_key<D: TransitDeserializer>(
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
         _deserializer: D,
         _input: D::Input,
     ) -> TResult<Self> {
@@ -61,14 +104,14 @@ where
     }
 }
 
-impl<K, V> TransitDeserialize for HashMap<K, V>
+impl<'de, K, V> TransitDeserialize<'de> for HashMap<K, V>
 where
-    K: TransitDeserialize + std::hash::Hash + Eq,
-    V: TransitDeserialize,
+    K: TransitDeserialize<'de> + std::hash::Hash + Eq,
+    V: TransitDeserialize<'de>,
 {
     const TF_TYPE: TransitType = TransitType::Composite;
 
-    fn transit_deserialize<D: TransitDeserializer>(
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
@@ -96,18 +139,15 @@ where
                     } else {
                         Err(Error::DoNotMatch(format!("{:?} must be ~#cmap", k_str)))
                     }?;
-                    let mut vals: Vec<D::Input> =
-                        deserializer.clone().deserialize_array(v)?.0.collect();
-                    for i in (0..vals.len()).step_by(2) {
+                    let mut vals = deserializer.clone().deserialize_array(v)?.0;
+                    while let Some(k) = vals.next() {
+                        let k = TransitDeserialize::transit_deserialize(deserializer.clone(), k)?;
+                        let v = vals.next().ok_or_else(|| {
+                            Error::DoNotMatch("~#cmap must have even number of items".to_owned())
+                        })?;
                         result.insert(
-                            TransitDeserialize::transit_deserialize(
-                                deserializer.clone(),
-                                mem::replace(&mut vals[i], D::Input::default()),
-                            )?,
-                            TransitDeserialize::transit_deserialize(
-                                deserializer.clone(),
-                                mem::replace(&mut vals[i + 1], D::Input::default()),
-                            )?,
+                            k,
+                            TransitDeserialize::transit_deserialize(deserializer.clone(), v)?,
                         );
                     }
                 }
@@ -116,7 +156,7 @@ where
         }
     }
 
-    fn transit_deserialize_key<D: TransitDeserializer>(
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
         _deserializer: D,
         _input: D::Input,
     ) -> TResult<Self> {
@@ -126,10 +166,10 @@ where
     }
 }
 
-impl<T: TransitDeserialize> TransitDeserialize for Vec<T> {
+impl<'de, T: TransitDeserialize<'de>> TransitDeserialize<'de> for Vec<T> {
     const TF_TYPE: TransitType = TransitType::Composite;
 
-    fn transit_deserialize<D: TransitDeserializer>(
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
@@ -148,7 +188,7 @@ impl<T: TransitDeserialize> TransitDeserialize for Vec<T> {
         Ok(v)
     }
 
-    fn transit_deserialize_key<D: TransitDeserializer>(
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
         _deserializer: D,
         _input: D::Input,
     ) -> TResult<Self> {
@@ -156,17 +196,72 @@ impl<T: TransitDeserialize> TransitDeserialize for Vec<T> {
     }
 }
 
-impl TransitDeserialize for bool {
+impl<'de, T: TransitDeserialize<'de> + Ord> TransitDeserialize<'de> for BTreeSet<T> {
+    const TF_TYPE: TransitType = TransitType::Composite;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let rep = deserialize_tagged(deserializer.clone(), input, "~#set")?;
+        let (array_iter, _) = deserializer.clone().deserialize_array(rep)?;
+        array_iter
+            .map(|x| TransitDeserialize::transit_deserialize(deserializer.clone(), x))
+            .collect()
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        _deserializer: D,
+        _input: D::Input,
+    ) -> TResult<Self> {
+        Err(Error::CannotBeKey(
+            "BTreeSet<T> cannot be deserialized as key",
+        ))
+    }
+}
+
+impl<'de, T: TransitDeserialize<'de> + std::hash::Hash + Eq> TransitDeserialize<'de>
+    for HashSet<T>
+{
+    const TF_TYPE: TransitType = TransitType::Composite;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let rep = deserialize_tagged(deserializer.clone(), input, "~#set")?;
+        let (array_iter, cap) = deserializer.clone().deserialize_array(rep)?;
+        let mut result = HashSet::with_capacity(cap.unwrap_or(0));
+        for x in array_iter {
+            result.insert(TransitDeserialize::transit_deserialize(
+                deserializer.clone(),
+                x,
+            )?);
+        }
+        Ok(result)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        _deserializer: D,
+        _input: D::Input,
+    ) -> TResult<Self> {
+        Err(Error::CannotBeKey(
+            "HashSet<T> cannot be deserialized as key",
+        ))
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for bool {
     const TF_TYPE: TransitType = TransitType::Scalar;
 
-    fn transit_deserialize<D: TransitDeserializer>(
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
         deserializer.deserialize_bool(input)
     }
 
-    fn transit_deserialize_key<D: TransitDeserializer>(
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
@@ -179,18 +274,18 @@ impl TransitDeserialize for bool {
     }
 }
 
-impl TransitDeserialize for i32 {
+impl<'de> TransitDeserialize<'de> for i32 {
     const TF_TYPE: TransitType = TransitType::Scalar;
 
-    fn transit_deserialize<D: TransitDeserializer>(
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
         Self::try_from(deserializer.deserialize_int(input)?)
-            .map_err(|_| Error::ItWontFit(format!("Cannot fit in i32")))
+            .map_err(|_| Error::ItWontFit("Cannot fit in i32".to_owned()))
     }
 
-    fn transit_deserialize_key<D: TransitDeserializer>(
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
@@ -199,33 +294,236 @@ impl TransitDeserialize for i32 {
         }
         let s = deserializer.deserialize_string(input)?;
         RE.captures(&s)
-            .ok_or(Error::DoNotMatch(format!("not proper i32 key",)))
+            .ok_or(Error::DoNotMatch("not proper i32 key".to_owned()))
             .and_then(|cap| {
                 cap.name("int")
-                    .ok_or(Error::DoNotMatch(format!("not proper i32 key",)))
+                    .ok_or(Error::DoNotMatch("not proper i32 key".to_owned()))
                     .and_then(|i| {
                         (i.as_str())
                             .parse::<Self>()
-                            .map_err(|_| Error::DoNotMatch(format!("not i32")))
+                            .map_err(|_| Error::DoNotMatch("not i32".to_owned()))
                     })
             })
     }
 }
 
-impl TransitDeserialize for String {
+impl<'de> TransitDeserialize<'de> for i64 {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        deserializer.deserialize_int(input)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let s = deserializer.deserialize_string(input)?;
+        s.strip_prefix("~i")
+            .and_then(|i| i.parse().ok())
+            .ok_or_else(|| Error::DoNotMatch(format!("{} is not proper i64 key", s)))
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for f64 {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        deserializer.deserialize_float(input)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let s = deserializer.deserialize_string(input)?;
+        s.strip_prefix("~d")
+            .and_then(|f| f.parse().ok())
+            .or_else(|| special_float(&s))
+            .ok_or_else(|| Error::DoNotMatch(format!("{} is not proper f64 key", s)))
+    }
+}
+
+/// Finite floats which overflow `f32` are not turned into infinity
+fn narrow_f32(f: f64) -> TResult<f32> {
+    let narrow = f as f32;
+    if f.is_finite() && !narrow.is_finite() {
+        Err(Error::ItWontFit(format!("{} cannot fit in f32", f)))
+    } else {
+        Ok(narrow)
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for f32 {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        f64::transit_deserialize(deserializer, input).and_then(narrow_f32)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        f64::transit_deserialize_key(deserializer, input).and_then(narrow_f32)
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for String {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let s = deserializer.deserialize_string(input)?;
+        Ok(unescape(s.into()).into_owned())
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+/// Fails unless the string is found in input as is, so it is only read from
+/// slices by `de::stream::from_slice` and `de::stream::from_str`
+impl<'de> TransitDeserialize<'de> for &'de str {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        borrowed(unescape(deserializer.deserialize_str(input)?))
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for Cow<'de, str> {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        deserializer.deserialize_str(input).map(unescape)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+/// Reads both `~t` ISO-8601 strings and `~m` milliseconds since epoch
+fn instant<'de, D: TransitDeserializer<'de>>(
+    deserializer: D,
+    input: D::Input,
+) -> TResult<DateTime<FixedOffset>> {
+    let s = deserializer.deserialize_string(input)?;
+    let not_instant = || Error::DoNotMatch(format!("{:?} is not an instant", s));
+    if let Some(t) = s.strip_prefix("~t") {
+        DateTime::parse_from_rfc3339(t).map_err(|_| not_instant())
+    } else if let Some(ms) = s.strip_prefix("~m") {
+        let ms = ms.parse().map_err(|_| not_instant())?;
+        Utc.timestamp_millis_opt(ms)
+            .single()
+            .map(|t| t.fixed_offset())
+            .ok_or_else(|| Error::ItWontFit(format!("{:?} is out of range", s)))
+    } else {
+        Err(not_instant())
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for DateTime<FixedOffset> {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        instant(deserializer, input)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        instant(deserializer, input)
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for DateTime<Utc> {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        instant(deserializer, input).map(|t| t.with_timezone(&Utc))
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+/// Instants are converted to UTC
+impl<'de> TransitDeserialize<'de> for NaiveDateTime {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        instant(deserializer, input).map(|t| t.naive_utc())
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for SystemTime {
     const TF_TYPE: TransitType = TransitType::Scalar;
 
-    fn transit_deserialize<D: TransitDeserializer>(
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
-        deserializer.deserialize_string(input).map(|x| x.to_owned())
+        instant(deserializer, input).map(SystemTime::from)
     }
 
-    fn transit_deserialize_key<D: TransitDeserializer>(
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
         deserializer: D,
         input: D::Input,
     ) -> TResult<Self> {
-        deserializer.deserialize_string(input).map(|x| x.to_owned())
+        Self::transit_deserialize(deserializer, input)
     }
 }
@@ -0,0 +1,303 @@
+use super::handlers::ReadHandlers;
+use super::*;
+use crate::cache::{ReadCache, MAP_AS_ARRAY};
+use serde_json::Value as JsVal;
+
+pub fn from_transit_json<T: TransitDeserializeOwned>(v: JsVal) -> TResult<T> {
+    deserialize(v, None)
+}
+
+/// Same as `from_transit_json`, with handlers of custom tags
+pub fn from_transit_json_with<T: TransitDeserializeOwned>(
+    v: JsVal,
+    handlers: &ReadHandlers,
+) -> TResult<T> {
+    deserialize(v, Some(handlers))
+}
+
+fn deserialize<T: TransitDeserializeOwned>(
+    v: JsVal,
+    handlers: Option<&ReadHandlers>,
+) -> TResult<T> {
+    let v = match read_cached(v, &mut ReadCache::default())? {
+        JsVal::Array(mut vec) if vec.len() == 2 && vec[0] == "~#'" => vec.pop().unwrap(),
+        v => v,
+    };
+    TransitDeserialize::transit_deserialize(JsonDeserializer { handlers }, v)
+}
+
+/// Resolves cache codes of the whole document in order of appearance,
+/// so deserialization itself is free to visit values in any order
+fn read_cached(v: JsVal, cache: &mut ReadCache<'_>) -> TResult<JsVal> {
+    let read_str = |s: String, cache: &mut ReadCache<'_>, as_map_key: bool| {
+        cache
+            .cache_read(Cow::Owned(s), as_map_key)
+            .map(Cow::into_owned)
+            .ok_or_else(|| Error::DoNotMatch("cache code is not in cache".to_owned()))
+    };
+    match v {
+        JsVal::String(s) => read_str(s, cache, false).map(JsVal::String),
+        JsVal::Array(vec) => {
+            let is_map = vec.first().and_then(|x| x.as_str()) == Some(MAP_AS_ARRAY);
+            vec.into_iter()
+                .enumerate()
+                .map(|(i, x)| match x {
+                    JsVal::String(s) if is_map && i % 2 == 1 => {
+                        read_str(s, cache, true).map(JsVal::String)
+                    }
+                    x => read_cached(x, cache),
+                })
+                .collect::<TResult<Vec<JsVal>>>()
+                .map(JsVal::Array)
+        }
+        JsVal::Object(m) => m
+            .into_iter()
+            .map(|(k, v)| Ok((read_str(k, cache, true)?, read_cached(v, cache)?)))
+            .collect::<TResult<_>>()
+            .map(JsVal::Object),
+        x => Ok(x),
+    }
+}
+
+fn is_tagged(vec: &[JsVal]) -> bool {
+    vec.len() == 2 && vec[0].as_str().is_some_and(|t| t.starts_with("~#"))
+}
+
+/// Iterates over interleaved keys and values of a map written as an array
+pub struct JsonMapIntoIter {
+    js_iter: std::vec::IntoIter<JsVal>,
+}
+
+impl Iterator for JsonMapIntoIter {
+    type Item = (JsVal, JsVal);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let k = self.js_iter.next()?;
+        let v = self.js_iter.next()?;
+        Some((k, v))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct JsonDeserializer<'h> {
+    handlers: Option<&'h ReadHandlers>,
+}
+
+impl<'de> TransitDeserializer<'de> for JsonDeserializer<'_> {
+    type Input = JsVal;
+    type DeserializeArray = std::vec::IntoIter<JsVal>;
+    type DeserializeMap = JsonMapIntoIter;
+
+    fn deserialize_string(self, v: Self::Input) -> TResult<String> {
+        if let JsVal::String(s) = v {
+            Ok(s)
+        } else {
+            Err(Error::DoNotMatch(format!("{} is not string", v)))
+        }
+    }
+
+    fn deserialize_bool(self, v: Self::Input) -> TResult<bool> {
+        v.as_bool()
+            .ok_or_else(|| Error::DoNotMatch(format!("{} is not bool", v)))
+    }
+
+    /// Integers which do not fit into JavaScript number are written as `~i` strings
+    fn deserialize_int(self, v: Self::Input) -> TResult<i64> {
+        v.as_i64()
+            .or_else(|| {
+                v.as_str()
+                    .filter(|s| s.starts_with("~i"))
+                    .and_then(|s| s[2..].parse().ok())
+            })
+            .ok_or_else(|| Error::DoNotMatch(format!("{} is not int", v)))
+    }
+
+    fn deserialize_float(self, v: Self::Input) -> TResult<f64> {
+        v.as_f64()
+            .or_else(|| v.as_str().and_then(special_float))
+            .ok_or_else(|| Error::DoNotMatch(format!("{} is not float", v)))
+    }
+
+    fn deserialize_array(self, v: Self::Input) -> TResult<(Self::DeserializeArray, Option<usize>)> {
+        match v {
+            JsVal::Array(vec)
+                if vec.first().and_then(|x| x.as_str()) != Some(MAP_AS_ARRAY)
+                    && !is_tagged(&vec) =>
+            {
+                let l = vec.len();
+                Ok((vec.into_iter(), Some(l)))
+            }
+            v => Err(Error::DoNotMatch(format!("{} is not an array", v))),
+        }
+    }
+
+    /// Tagged values `["~#tag", rep]` are read as a map with a single entry
+    /// to match their verbose representation
+    fn deserialize_map(self, v: Self::Input) -> TResult<(Self::DeserializeMap, Option<usize>)> {
+        match v {
+            JsVal::Array(mut vec) if vec.first().and_then(|x| x.as_str()) == Some(MAP_AS_ARRAY) => {
+                if vec.len() % 2 == 0 {
+                    return Err(Error::CannotParse(format!(
+                        "map key {} has no value",
+                        vec[vec.len() - 1]
+                    )));
+                }
+                let l = (vec.len() - 1) / 2;
+                let js_iter = vec.split_off(1).into_iter();
+                Ok((JsonMapIntoIter { js_iter }, Some(l)))
+            }
+            JsVal::Array(vec) if is_tagged(&vec) => Ok((
+                JsonMapIntoIter {
+                    js_iter: vec.into_iter(),
+                },
+                Some(1),
+            )),
+            JsVal::Object(m) => {
+                let l = m.len();
+                let js_iter: Vec<JsVal> = m
+                    .into_iter()
+                    .flat_map(|(k, v)| vec![JsVal::String(k), v])
+                    .collect();
+                Ok((
+                    JsonMapIntoIter {
+                        js_iter: js_iter.into_iter(),
+                    },
+                    Some(l),
+                ))
+            }
+            v => Err(Error::DoNotMatch(format!("{} is not a map", v))),
+        }
+    }
+
+    fn input_kind(&self, v: &Self::Input) -> TResult<InputKind> {
+        Ok(match v {
+            JsVal::Null => InputKind::Null,
+            JsVal::Bool(_) => InputKind::Bool,
+            JsVal::Number(n) if n.is_f64() => InputKind::Float,
+            JsVal::Number(_) => InputKind::Int,
+            JsVal::String(_) => InputKind::String,
+            JsVal::Array(vec)
+                if vec.first().and_then(|x| x.as_str()) == Some(MAP_AS_ARRAY) || is_tagged(vec) =>
+            {
+                InputKind::Map
+            }
+            JsVal::Array(_) => InputKind::Array,
+            JsVal::Object(_) => InputKind::Map,
+        })
+    }
+
+    fn read_handlers(&self) -> Option<&ReadHandlers> {
+        self.handlers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ser::json::to_transit_json;
+    use serde_json::json;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn dumb_array() {
+        let tr: Vec<i32> = from_transit_json(json!([1, 2, 3])).unwrap();
+        assert_eq!(vec![1, 2, 3], tr);
+    }
+
+    #[test]
+    fn quoting() {
+        let tr: i32 = from_transit_json(json!(["~#'", 5])).unwrap();
+        assert_eq!(5, tr);
+    }
+
+    #[test]
+    fn scalar_map_btree() {
+        let mut m = BTreeMap::new();
+        m.insert(4, "yolo".to_owned());
+        m.insert(-6, "swag".to_owned());
+
+        let tr = from_transit_json(json!(["^ ", "~i4", "yolo", "~i-6", "swag"])).unwrap();
+        assert_eq!(m, tr);
+    }
+
+    #[test]
+    fn map_composite_keys() {
+        let mut key1: BTreeMap<bool, String> = BTreeMap::new();
+        key1.insert(true, "test".to_owned());
+        key1.insert(false, "tset".to_owned());
+
+        let mut m = HashMap::new();
+        m.insert(key1, 1337);
+
+        let tr: HashMap<BTreeMap<bool, String>, i32> = from_transit_json(json!([
+            "~#cmap",
+            [["^ ", "~?t", "test", "~?f", "tset"], 1337]
+        ]))
+        .unwrap();
+        assert_eq!(m, tr);
+    }
+
+    #[test]
+    fn read_cache() {
+        let tr: Vec<BTreeMap<String, BTreeSet<String>>> = from_transit_json(json!([
+            ["^ ", "langs", ["~#set", ["~~rust", "clojure"]]],
+            ["^ ", "^0", ["^1", ["~^lisp"]]]
+        ]))
+        .unwrap();
+
+        let mut m1 = BTreeMap::new();
+        m1.insert(
+            "langs".to_owned(),
+            vec!["~rust".to_owned(), "clojure".to_owned()]
+                .into_iter()
+                .collect(),
+        );
+        let mut m2 = BTreeMap::new();
+        m2.insert(
+            "langs".to_owned(),
+            vec!["^lisp".to_owned()].into_iter().collect(),
+        );
+        assert_eq!(vec![m1, m2], tr);
+    }
+
+    #[test]
+    fn unknown_cache_code() {
+        let tr: TResult<Vec<String>> = from_transit_json(json!(["^0"]));
+        assert!(tr.is_err());
+    }
+
+    #[test]
+    fn odd_map() {
+        let tr: TResult<BTreeMap<String, i32>> = from_transit_json(json!(["^ ", "a", 1, "b"]));
+        assert!(matches!(tr, Err(Error::CannotParse(_))));
+    }
+
+    #[test]
+    fn narrow_floats() {
+        let tr: TResult<Vec<f32>> = from_transit_json(json!([1e300]));
+        assert!(matches!(tr, Err(Error::ItWontFit(_))));
+        let tr: TResult<Vec<f32>> = from_transit_json(json!([-1e300]));
+        assert!(matches!(tr, Err(Error::ItWontFit(_))));
+        let tr: Vec<f32> = from_transit_json(json!([1.5, "~zINF"])).unwrap();
+        assert_eq!(vec![1.5, f32::INFINITY], tr);
+    }
+
+    #[test]
+    fn verbose_map() {
+        let mut m = BTreeMap::new();
+        m.insert(true, 1);
+
+        let tr = from_transit_json(json!({ "~?t": 1 })).unwrap();
+        assert_eq!(m, tr);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut m: BTreeMap<i32, BTreeSet<String>> = BTreeMap::new();
+        for i in 0..100 {
+            m.insert(i, (0..i).map(|x| format!("~value {}", x % 7)).collect());
+        }
+        let tr = from_transit_json(to_transit_json(&m)).unwrap();
+        assert_eq!(m, tr);
+    }
+}
@@ -1,8 +1,35 @@
+use super::handlers::ReadHandlers;
 use super::*;
 use serde_json::{map::IntoIter as JsMapIntoIter, Value as JsVal};
 
-pub fn from_transit_json<T: TransitDeserialize>(v: JsVal) -> TResult<T> {
-    TransitDeserialize::transit_deserialize(JsonDeserializer, v)
+pub fn from_transit_json<T: TransitDeserializeOwned>(v: JsVal) -> TResult<T> {
+    TransitDeserialize::transit_deserialize(JsonDeserializer::default(), unquote(v))
+}
+
+/// Same as `from_transit_json`, with handlers of custom tags
+pub fn from_transit_json_with<T: TransitDeserializeOwned>(
+    v: JsVal,
+    handlers: &ReadHandlers,
+) -> TResult<T> {
+    let handlers = Some(handlers);
+    TransitDeserialize::transit_deserialize(JsonDeserializer { handlers }, unquote(v))
+}
+
+/// Reads any `serde::Deserialize` type, see `ser::serde_bridge` for how
+/// serde data model is mapped onto Transit
+pub fn from_transit_json_serde<T: serde::de::DeserializeOwned>(v: JsVal) -> TResult<T> {
+    T::deserialize(serde_bridge::Deserializer::new(
+        JsonDeserializer::default(),
+        unquote(v),
+    ))
+}
+
+/// Unwraps a scalar quoted at top level, `{"~#'": 5}`
+fn unquote(v: JsVal) -> JsVal {
+    match v {
+        JsVal::Object(mut m) if m.len() == 1 && m.contains_key("~#'") => m.remove("~#'").unwrap(),
+        v => v,
+    }
 }
 
 struct JsonObjectIntoIter {
@@ -17,18 +44,22 @@ impl Iterator for JsonObjectIntoIter {
     }
 }
 
-#[derive(Clone, Debug)]
-struct JsonDeserializer;
+#[derive(Clone, Copy, Debug, Default)]
+struct JsonDeserializer<'h> {
+    handlers: Option<&'h ReadHandlers>,
+}
 
-impl TransitDeserializer for JsonDeserializer {
+impl<'de> TransitDeserializer<'de> for JsonDeserializer<'_> {
     type Input = JsVal;
     type DeserializeArray = std::vec::IntoIter<JsVal>;
     type DeserializeMap = JsonObjectIntoIter;
 
     fn deserialize_string(self, v: Self::Input) -> TResult<String> {
-        v.as_str()
-            .map(|x| x.to_owned())
-            .ok_or(Error::DoNotMatch(format!("{} is not string", v)))
+        if let JsVal::String(s) = v {
+            Ok(s)
+        } else {
+            Err(Error::DoNotMatch(format!("{} is not string", v)))
+        }
     }
 
     fn deserialize_bool(self, v: Self::Input) -> TResult<bool> {
@@ -36,13 +67,20 @@ impl TransitDeserializer for JsonDeserializer {
             .ok_or(Error::DoNotMatch(format!("{} is not bool", v)))
     }
 
+    /// Integers which do not fit into JavaScript number are written as `~i` strings
     fn deserialize_int(self, v: Self::Input) -> TResult<i64> {
         v.as_i64()
-            .ok_or(Error::DoNotMatch(format!("{} is not int", v)))
+            .or_else(|| {
+                v.as_str()
+                    .filter(|s| s.starts_with("~i"))
+                    .and_then(|s| s[2..].parse().ok())
+            })
+            .ok_or_else(|| Error::DoNotMatch(format!("{} is not int", v)))
     }
 
     fn deserialize_float(self, v: Self::Input) -> TResult<f64> {
         v.as_f64()
+            .or_else(|| v.as_str().and_then(special_float))
             .ok_or(Error::DoNotMatch(format!("{} is not float", v)))
     }
 
@@ -68,6 +106,22 @@ impl TransitDeserializer for JsonDeserializer {
             Err(Error::DoNotMatch(format!("{} is not a map", v)))
         }
     }
+
+    fn input_kind(&self, v: &Self::Input) -> TResult<InputKind> {
+        Ok(match v {
+            JsVal::Null => InputKind::Null,
+            JsVal::Bool(_) => InputKind::Bool,
+            JsVal::Number(n) if n.is_f64() => InputKind::Float,
+            JsVal::Number(_) => InputKind::Int,
+            JsVal::String(_) => InputKind::String,
+            JsVal::Array(_) => InputKind::Array,
+            JsVal::Object(_) => InputKind::Map,
+        })
+    }
+
+    fn read_handlers(&self) -> Option<&ReadHandlers> {
+        self.handlers
+    }
 }
 
 #[cfg(test)]
@@ -86,6 +140,15 @@ mod test {
     // TODO: Quoting
     // Check that something like 5 cannot be parsed on top level
 
+    #[test]
+    fn quoting() {
+        let tr: i32 = from_transit_json(json!({"~#'": 5})).unwrap();
+        assert_eq!(5, tr);
+        let tr: String =
+            from_transit_json(crate::ser::json_verbose::to_transit_json("~x")).unwrap();
+        assert_eq!("~x", tr);
+    }
+
     #[test]
     fn scalar_map_btree() {
         let mut m = BTreeMap::new();
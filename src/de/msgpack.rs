@@ -0,0 +1,284 @@
+use super::handlers::ReadHandlers;
+use super::*;
+use crate::cache::{ReadCache, MAP_AS_ARRAY};
+use rmpv::Value as MpVal;
+
+/// Deserializes bytes of `application/transit+msgpack` content type
+pub fn from_transit_msgpack<T: TransitDeserializeOwned>(mut v: &[u8]) -> TResult<T> {
+    let value = rmpv::decode::read_value(&mut v).map_err(|e| Error::CannotParse(e.to_string()))?;
+    from_transit_msgpack_value(value)
+}
+
+pub fn from_transit_msgpack_value<T: TransitDeserializeOwned>(v: MpVal) -> TResult<T> {
+    deserialize(v, None)
+}
+
+/// Same as `from_transit_msgpack`, with handlers of custom tags
+pub fn from_transit_msgpack_with<T: TransitDeserializeOwned>(
+    mut v: &[u8],
+    handlers: &ReadHandlers,
+) -> TResult<T> {
+    let value = rmpv::decode::read_value(&mut v).map_err(|e| Error::CannotParse(e.to_string()))?;
+    deserialize(value, Some(handlers))
+}
+
+fn deserialize<T: TransitDeserializeOwned>(
+    v: MpVal,
+    handlers: Option<&ReadHandlers>,
+) -> TResult<T> {
+    let v = match read_cached(v, &mut ReadCache::default())? {
+        MpVal::Array(mut vec) if vec.len() == 2 && vec[0].as_str() == Some("~#'") => {
+            vec.pop().unwrap()
+        }
+        v => v,
+    };
+    TransitDeserialize::transit_deserialize(MsgpackDeserializer { handlers }, v)
+}
+
+/// Resolves cache codes of the whole document in order of appearance,
+/// so deserialization itself is free to visit values in any order
+fn read_cached(v: MpVal, cache: &mut ReadCache<'_>) -> TResult<MpVal> {
+    let read_str = |s: rmpv::Utf8String, cache: &mut ReadCache<'_>, as_map_key: bool| {
+        let s = s
+            .into_str()
+            .ok_or_else(|| Error::CannotParse("string is not valid UTF-8".to_owned()))?;
+        cache
+            .cache_read(Cow::Owned(s), as_map_key)
+            .map(|x| MpVal::from(x.into_owned()))
+            .ok_or_else(|| Error::DoNotMatch("cache code is not in cache".to_owned()))
+    };
+    match v {
+        MpVal::String(s) => read_str(s, cache, false),
+        MpVal::Array(vec) => {
+            let is_map = vec.first().and_then(|x| x.as_str()) == Some(MAP_AS_ARRAY);
+            vec.into_iter()
+                .enumerate()
+                .map(|(i, x)| match x {
+                    MpVal::String(s) if is_map && i % 2 == 1 => read_str(s, cache, true),
+                    x => read_cached(x, cache),
+                })
+                .collect::<TResult<Vec<MpVal>>>()
+                .map(MpVal::Array)
+        }
+        MpVal::Map(m) => m
+            .into_iter()
+            .map(|(k, v)| {
+                let k = match k {
+                    MpVal::String(s) => read_str(s, cache, true)?,
+                    k => read_cached(k, cache)?,
+                };
+                Ok((k, read_cached(v, cache)?))
+            })
+            .collect::<TResult<Vec<(MpVal, MpVal)>>>()
+            .map(MpVal::Map),
+        x => Ok(x),
+    }
+}
+
+fn is_tagged(vec: &[MpVal]) -> bool {
+    vec.len() == 2 && vec[0].as_str().is_some_and(|t| t.starts_with("~#"))
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct MsgpackDeserializer<'h> {
+    handlers: Option<&'h ReadHandlers>,
+}
+
+impl<'de> TransitDeserializer<'de> for MsgpackDeserializer<'_> {
+    type Input = MpVal;
+    type DeserializeArray = std::vec::IntoIter<MpVal>;
+    type DeserializeMap = std::vec::IntoIter<(MpVal, MpVal)>;
+
+    fn deserialize_string(self, v: Self::Input) -> TResult<String> {
+        match v {
+            MpVal::String(s) if s.is_str() => Ok(s.into_str().unwrap()),
+            v => Err(Error::DoNotMatch(format!("{} is not string", v))),
+        }
+    }
+
+    fn deserialize_bool(self, v: Self::Input) -> TResult<bool> {
+        v.as_bool()
+            .ok_or_else(|| Error::DoNotMatch(format!("{} is not bool", v)))
+    }
+
+    fn deserialize_int(self, v: Self::Input) -> TResult<i64> {
+        match v {
+            MpVal::Integer(i) => i
+                .as_i64()
+                .ok_or_else(|| Error::ItWontFit(format!("{} cannot fit in i64", i))),
+            v => Err(Error::DoNotMatch(format!("{} is not int", v))),
+        }
+    }
+
+    fn deserialize_float(self, v: Self::Input) -> TResult<f64> {
+        v.as_f64()
+            .or_else(|| v.as_str().and_then(special_float))
+            .ok_or_else(|| Error::DoNotMatch(format!("{} is not float", v)))
+    }
+
+    fn deserialize_array(self, v: Self::Input) -> TResult<(Self::DeserializeArray, Option<usize>)> {
+        match v {
+            MpVal::Array(vec)
+                if vec.first().and_then(|x| x.as_str()) != Some(MAP_AS_ARRAY)
+                    && !is_tagged(&vec) =>
+            {
+                let l = vec.len();
+                Ok((vec.into_iter(), Some(l)))
+            }
+            v => Err(Error::DoNotMatch(format!("{} is not an array", v))),
+        }
+    }
+
+    /// Both native MessagePack maps and maps written as arrays are accepted.
+    /// Tagged values `["~#tag", rep]` are read as a map with a single entry.
+    fn deserialize_map(self, v: Self::Input) -> TResult<(Self::DeserializeMap, Option<usize>)> {
+        match v {
+            MpVal::Map(m) => {
+                let l = m.len();
+                Ok((m.into_iter(), Some(l)))
+            }
+            MpVal::Array(vec)
+                if vec.first().and_then(|x| x.as_str()) == Some(MAP_AS_ARRAY)
+                    || is_tagged(&vec) =>
+            {
+                let skip = if is_tagged(&vec) { 0 } else { 1 };
+                if (vec.len() - skip) % 2 == 1 {
+                    return Err(Error::CannotParse(format!(
+                        "map key {} has no value",
+                        vec[vec.len() - 1]
+                    )));
+                }
+                let mut items = vec.into_iter().skip(skip);
+                let mut pairs = Vec::with_capacity(items.len() / 2);
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    pairs.push((k, v));
+                }
+                let l = pairs.len();
+                Ok((pairs.into_iter(), Some(l)))
+            }
+            v => Err(Error::DoNotMatch(format!("{} is not a map", v))),
+        }
+    }
+
+    fn input_kind(&self, v: &Self::Input) -> TResult<InputKind> {
+        Ok(match v {
+            MpVal::Nil => InputKind::Null,
+            MpVal::Boolean(_) => InputKind::Bool,
+            MpVal::Integer(_) => InputKind::Int,
+            MpVal::F32(_) | MpVal::F64(_) => InputKind::Float,
+            MpVal::String(_) => InputKind::String,
+            MpVal::Array(vec)
+                if vec.first().and_then(|x| x.as_str()) == Some(MAP_AS_ARRAY) || is_tagged(vec) =>
+            {
+                InputKind::Map
+            }
+            MpVal::Array(_) => InputKind::Array,
+            MpVal::Map(_) => InputKind::Map,
+            MpVal::Binary(_) => InputKind::Bytes,
+            v => return Err(Error::DoNotMatch(format!("{} is not supported", v))),
+        })
+    }
+
+    fn deserialize_bytes(self, v: Self::Input) -> TResult<Vec<u8>> {
+        match v {
+            MpVal::Binary(b) => Ok(b),
+            v => bytes_from_str(&self.deserialize_string(v)?),
+        }
+    }
+
+    fn read_handlers(&self) -> Option<&ReadHandlers> {
+        self.handlers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ser::msgpack::to_transit_msgpack;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn ints() {
+        let tr: Vec<i32> = from_transit_msgpack(&[0x93, 0x01, 0xff, 0xd1, 0x05, 0x39]).unwrap();
+        assert_eq!(vec![1, -1, 1337], tr);
+    }
+
+    #[test]
+    fn wide_ints() {
+        let v = vec![i64::MIN, i64::MAX];
+        let tr: Vec<i64> = from_transit_msgpack(&to_transit_msgpack(&v)).unwrap();
+        assert_eq!(v, tr);
+
+        let tr: TResult<Vec<i64>> = from_transit_msgpack(&to_transit_msgpack_u64());
+        match tr {
+            Err(Error::ItWontFit(_)) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    fn to_transit_msgpack_u64() -> Vec<u8> {
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, &MpVal::Array(vec![MpVal::from(u64::MAX)])).unwrap();
+        buf
+    }
+
+    #[test]
+    fn quoting() {
+        let tr: i32 = from_transit_msgpack(&to_transit_msgpack(5)).unwrap();
+        assert_eq!(5, tr);
+    }
+
+    #[test]
+    fn native_map() {
+        let v = MpVal::Map(vec![
+            (MpVal::from("~i1"), MpVal::from("one")),
+            (MpVal::from("~i2"), MpVal::from("two")),
+        ]);
+        let tr: BTreeMap<i32, String> = from_transit_msgpack_value(v).unwrap();
+
+        let mut m = BTreeMap::new();
+        m.insert(1, "one".to_owned());
+        m.insert(2, "two".to_owned());
+        assert_eq!(m, tr);
+    }
+
+    #[test]
+    fn odd_map() {
+        let v = MpVal::Array(vec![
+            MpVal::from("^ "),
+            MpVal::from("a"),
+            MpVal::from(1),
+            MpVal::from("b"),
+        ]);
+        let tr: TResult<BTreeMap<String, i32>> = from_transit_msgpack_value(v);
+        match tr {
+            Err(Error::CannotParse(_)) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let tr: TResult<Vec<i32>> = from_transit_msgpack(&[0x93, 0x01]);
+        match tr {
+            Err(Error::CannotParse(_)) => (),
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut key1: BTreeMap<bool, String> = BTreeMap::new();
+        key1.insert(true, "test".to_owned());
+
+        let mut m: BTreeMap<BTreeMap<bool, String>, BTreeSet<String>> = BTreeMap::new();
+        m.insert(
+            key1,
+            vec!["~:keyword-like".to_owned(), "value".to_owned()]
+                .into_iter()
+                .collect(),
+        );
+        let tr = from_transit_msgpack(&to_transit_msgpack(&m)).unwrap();
+        assert_eq!(m, tr);
+    }
+}
@@ -0,0 +1,501 @@
+//! Adapter which lets `serde::Deserialize` types be read from Transit by
+//! any backend, the inverse of `ser::serde_bridge`.
+//!
+//! Tags of structs and enum variants are matched case-insensitively, keys
+//! like `~i4` and `~?t` are decoded to native types and `~#set`/`~#list`
+//! are read as sequences.
+
+use super::*;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use std::marker::PhantomData;
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+/// `serde::Deserializer` over a single input of a Transit deserializer
+pub(crate) struct Deserializer<'de, D: TransitDeserializer<'de>> {
+    de: D,
+    input: D::Input,
+    lifetime: PhantomData<&'de ()>,
+}
+
+impl<'de, D: TransitDeserializer<'de>> Deserializer<'de, D> {
+    pub(crate) fn new(de: D, input: D::Input) -> Self {
+        Deserializer {
+            de,
+            input,
+            lifetime: PhantomData,
+        }
+    }
+
+    /// Maps are opened to look at their first key, which tells tagged
+    /// values apart
+    fn open(self) -> TResult<Opened<'de, D>> {
+        let de = self.de;
+        let content = match de.input_kind(&self.input)? {
+            InputKind::Array => Content::Array(self.input),
+            InputKind::Map => {
+                let (mut iter, _) = de.clone().deserialize_map(self.input)?;
+                match iter.next() {
+                    Some((k, v)) => {
+                        let k = de.clone().deserialize_string(k)?;
+                        match k.strip_prefix("~#") {
+                            Some(tag) => Content::Tagged(tag.to_owned(), v),
+                            None => Content::Map(Some((k, v)), iter),
+                        }
+                    }
+                    None => Content::Map(None, iter),
+                }
+            }
+            _ => Content::Scalar(self.input),
+        };
+        Ok(Opened { de, content })
+    }
+}
+
+enum Content<'de, D: TransitDeserializer<'de>> {
+    Scalar(D::Input),
+    Array(D::Input),
+    Map(Option<(String, D::Input)>, D::DeserializeMap),
+    Tagged(String, D::Input),
+}
+
+struct Opened<'de, D: TransitDeserializer<'de>> {
+    de: D,
+    content: Content<'de, D>,
+}
+
+/// Finds the variant a lowercase tag was written for
+fn variant_name(tag: &str, variants: &'static [&'static str]) -> String {
+    variants
+        .iter()
+        .find(|v| v.eq_ignore_ascii_case(tag))
+        .map_or_else(|| tag.to_owned(), |v| (*v).to_owned())
+}
+
+impl<'de, D: TransitDeserializer<'de>> de::Deserializer<'de> for Deserializer<'de, D> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> TResult<V::Value> {
+        self.open()?.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> TResult<V::Value> {
+        self.open()?.deserialize_option(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> TResult<V::Value> {
+        self.open()?.deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> TResult<V::Value> {
+        self.open()?.deserialize_enum(name, variants, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de, D: TransitDeserializer<'de>> de::Deserializer<'de> for Opened<'de, D> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> TResult<V::Value> {
+        match self.content {
+            Content::Scalar(input) => match self.de.input_kind(&input)? {
+                InputKind::Null => visitor.visit_unit(),
+                InputKind::Bool => visitor.visit_bool(self.de.deserialize_bool(input)?),
+                InputKind::Int => visitor.visit_i64(self.de.deserialize_int(input)?),
+                InputKind::Float => visitor.visit_f64(self.de.deserialize_float(input)?),
+                InputKind::Bytes => visitor.visit_byte_buf(self.de.deserialize_bytes(input)?),
+                _ => match self.de.deserialize_str(input)? {
+                    Cow::Borrowed(s) if !s.starts_with('~') => visitor.visit_borrowed_str(s),
+                    s => StrDeserializer(s.into_owned()).deserialize_any(visitor),
+                },
+            },
+            Content::Array(input) => visitor.visit_seq(SeqAccess::new(self.de, input)?),
+            Content::Map(first, iter) => visitor.visit_map(MapAccess {
+                de: self.de,
+                first,
+                iter,
+                value: None,
+            }),
+            Content::Tagged(tag, rep) => match tag.as_str() {
+                "set" | "list" => visitor.visit_seq(SeqAccess::new(self.de, rep)?),
+                "cmap" => visitor.visit_map(CmapAccess {
+                    seq: SeqAccess::new(self.de, rep)?,
+                }),
+                // Other tags, including quotes, are only hints for
+                // statically typed readers
+                _ => Deserializer::new(self.de, rep).deserialize_any(visitor),
+            },
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> TResult<V::Value> {
+        match &self.content {
+            Content::Scalar(input) if self.de.input_kind(input)? == InputKind::Null => {
+                visitor.visit_none()
+            }
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    /// Reads `{"~#name": [v]}` written by `ser::serde_bridge` as well as
+    /// bare inner values
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> TResult<V::Value> {
+        match self.content {
+            Content::Tagged(tag, rep) if tag.eq_ignore_ascii_case(name) => {
+                let mut seq = SeqAccess::new(self.de, rep)?;
+                let v = seq.single()?;
+                visitor.visit_newtype_struct(v)
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> TResult<V::Value> {
+        match self.content {
+            Content::Scalar(input) => {
+                let s = self.de.deserialize_string(input)?;
+                let tag = s.strip_prefix("~:").unwrap_or(&s);
+                visitor.visit_enum(variant_name(tag, variants).into_deserializer())
+            }
+            Content::Tagged(tag, rep) => visitor.visit_enum(EnumAccess {
+                variant: variant_name(&tag, variants),
+                de: Deserializer::new(self.de, rep),
+            }),
+            _ => Err(Error::DoNotMatch(
+                "enum must be a keyword or a tagged value".to_owned(),
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// Transit string which may encode a scalar of any type, e.g. a map key
+struct StrDeserializer(String);
+
+impl<'de> de::Deserializer<'de> for StrDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> TResult<V::Value> {
+        let s = self.0;
+        let mut chars = s.chars();
+        let tag = match (chars.next(), chars.next()) {
+            (Some('~'), Some(tag)) => tag,
+            _ => return visitor.visit_string(s),
+        };
+        let rep = chars.as_str();
+        let not_match = || Error::DoNotMatch(format!("{:?} cannot be decoded", s));
+        match tag {
+            '~' | '^' | '`' => visitor.visit_str(&s[1..]),
+            '_' => visitor.visit_unit(),
+            '?' => match rep {
+                "t" => visitor.visit_bool(true),
+                "f" => visitor.visit_bool(false),
+                _ => Err(not_match()),
+            },
+            'i' => visitor.visit_i64(rep.parse().map_err(|_| not_match())?),
+            'n' => match (rep.parse::<i64>(), rep.parse::<u64>()) {
+                (Ok(i), _) => visitor.visit_i64(i),
+                (_, Ok(u)) => visitor.visit_u64(u),
+                _ => visitor.visit_str(rep),
+            },
+            'd' => visitor.visit_f64(rep.parse().map_err(|_| not_match())?),
+            'z' => visitor.visit_f64(special_float(&s).ok_or_else(not_match)?),
+            'c' => match rep.chars().next() {
+                Some(c) if c.len_utf8() == rep.len() => visitor.visit_char(c),
+                _ => Err(not_match()),
+            },
+            'b' => visitor.visit_byte_buf(BASE64.decode(rep).map_err(|_| not_match())?),
+            // Keywords, symbols, timestamps, UUIDs and URIs are read as
+            // their string representation
+            _ => visitor.visit_str(rep),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> TResult<V::Value> {
+        if self.0 == "~_" {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> TResult<V::Value> {
+        let tag = self.0.strip_prefix("~:").unwrap_or(&self.0);
+        visitor.visit_enum(variant_name(tag, variants).into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
+
+struct SeqAccess<'de, D: TransitDeserializer<'de>> {
+    de: D,
+    iter: D::DeserializeArray,
+    len: Option<usize>,
+}
+
+impl<'de, D: TransitDeserializer<'de>> SeqAccess<'de, D> {
+    fn new(de: D, input: D::Input) -> TResult<Self> {
+        let (iter, len) = de.clone().deserialize_array(input)?;
+        Ok(SeqAccess { de, iter, len })
+    }
+
+    fn next_input(&mut self) -> Option<D::Input> {
+        self.len = self.len.map(|l| l.saturating_sub(1));
+        self.iter.next()
+    }
+
+    /// Content of a single item array, e.g. of a newtype
+    fn single(&mut self) -> TResult<Deserializer<'de, D>> {
+        match (self.next_input(), self.len) {
+            (Some(v), Some(0)) | (Some(v), None) => Ok(Deserializer::new(self.de.clone(), v)),
+            _ => Err(Error::DoNotMatch(
+                "newtype must be an array of one item".to_owned(),
+            )),
+        }
+    }
+}
+
+impl<'de, D: TransitDeserializer<'de>> de::SeqAccess<'de> for SeqAccess<'de, D> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> TResult<Option<T::Value>> {
+        match self.next_input() {
+            Some(v) => seed
+                .deserialize(Deserializer::new(self.de.clone(), v))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.len
+    }
+}
+
+/// Keys of ordinary maps are always strings
+struct MapAccess<'de, D: TransitDeserializer<'de>> {
+    de: D,
+    first: Option<(String, D::Input)>,
+    iter: D::DeserializeMap,
+    value: Option<D::Input>,
+}
+
+impl<'de, D: TransitDeserializer<'de>> de::MapAccess<'de> for MapAccess<'de, D> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> TResult<Option<K::Value>> {
+        let (k, v) = match self.first.take() {
+            Some(pair) => pair,
+            None => match self.iter.next() {
+                Some((k, v)) => (self.de.clone().deserialize_string(k)?, v),
+                None => return Ok(None),
+            },
+        };
+        self.value = Some(v);
+        seed.deserialize(StrDeserializer(k)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> TResult<V::Value> {
+        let v = self
+            .value
+            .take()
+            .ok_or_else(|| Error::Custom("map value requested before its key".to_owned()))?;
+        seed.deserialize(Deserializer::new(self.de.clone(), v))
+    }
+}
+
+/// `~#cmap` is an array of interleaved keys and values of any type
+struct CmapAccess<'de, D: TransitDeserializer<'de>> {
+    seq: SeqAccess<'de, D>,
+}
+
+impl<'de, D: TransitDeserializer<'de>> de::MapAccess<'de> for CmapAccess<'de, D> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> TResult<Option<K::Value>> {
+        de::SeqAccess::next_element_seed(&mut self.seq, seed)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> TResult<V::Value> {
+        de::SeqAccess::next_element_seed(&mut self.seq, seed)?
+            .ok_or_else(|| Error::DoNotMatch("~#cmap must have even number of items".to_owned()))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.seq.len.map(|l| l / 2)
+    }
+}
+
+struct EnumAccess<'de, D: TransitDeserializer<'de>> {
+    variant: String,
+    de: Deserializer<'de, D>,
+}
+
+impl<'de, D: TransitDeserializer<'de>> de::EnumAccess<'de> for EnumAccess<'de, D> {
+    type Error = Error;
+    type Variant = Deserializer<'de, D>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> TResult<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self.de))
+    }
+}
+
+impl<'de, D: TransitDeserializer<'de>> de::VariantAccess<'de> for Deserializer<'de, D> {
+    type Error = Error;
+
+    fn unit_variant(self) -> TResult<()> {
+        Ok(())
+    }
+
+    /// Written as an array of one item, same as newtype structs
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> TResult<T::Value> {
+        if self.de.input_kind(&self.input)? == InputKind::Array {
+            seed.deserialize(SeqAccess::new(self.de, self.input)?.single()?)
+        } else {
+            seed.deserialize(self)
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> TResult<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> TResult<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::de::json_verbose::from_transit_json_serde;
+    use crate::to_transit_json_serde;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Meters(f64);
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Shape {
+        Empty,
+        Circle(Meters),
+        Line(Point, Point),
+        Rect { corner: Point, width: u64 },
+    }
+
+    #[test]
+    fn keys_and_sets() {
+        let tr: BTreeMap<i32, BTreeSet<String>> =
+            from_transit_json_serde(json!({"~i4": {"~#set": ["~~a", "b"]}, "~i-1": {"~#set": []}}))
+                .unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(
+            4,
+            vec!["~a".to_owned(), "b".to_owned()].into_iter().collect(),
+        );
+        m.insert(-1, BTreeSet::new());
+        assert_eq!(m, tr);
+
+        let tr: BTreeMap<bool, Option<char>> =
+            from_transit_json_serde(json!({"~?t": "~cx", "~?f": null})).unwrap();
+        assert_eq!(Some(&Some('x')), tr.get(&true));
+        assert_eq!(Some(&None), tr.get(&false));
+    }
+
+    #[test]
+    fn composite_keys() {
+        let tr: BTreeMap<(i32, bool), Vec<i64>> =
+            from_transit_json_serde(json!({"~#cmap": [[1, true], {"~#list": [2, 3]}]})).unwrap();
+        let mut m = BTreeMap::new();
+        m.insert((1, true), vec![2, 3]);
+        assert_eq!(m, tr);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Circle(Meters(2.5)),
+            Shape::Line(Point { x: 0, y: 1 }, Point { x: 2, y: 3 }),
+            Shape::Rect {
+                corner: Point { x: -1, y: -1 },
+                width: u64::MAX,
+            },
+        ];
+        let tr: Vec<Shape> =
+            crate::from_transit_json_serde(to_transit_json_serde(&shapes).unwrap()).unwrap();
+        assert_eq!(shapes, tr);
+
+        let quoted: Point =
+            from_transit_json_serde(json!({"~#'": {"~#point": {"x": 1, "y": 2}}})).unwrap();
+        assert_eq!(Point { x: 1, y: 2 }, quoted);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(from_transit_json_serde::<Point>(json!({"x": "1", "y": 2})).is_err());
+        assert!(from_transit_json_serde::<Shape>(json!([1])).is_err());
+        assert!(from_transit_json_serde::<Meters>(json!({"~#meters": [1, 2]})).is_err());
+        for s in &["~?x", "~zINFINITY"] {
+            match from_transit_json_serde::<Vec<serde_json::Value>>(json!([s])) {
+                Err(crate::de::Error::DoNotMatch(_)) => (),
+                tr => panic!("{} was read as {:?}", s, tr),
+            }
+        }
+        assert!(from_transit_json_serde::<Vec<bool>>(json!(["~?x"])).is_err());
+    }
+}
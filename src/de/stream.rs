@@ -0,0 +1,1240 @@
+//! Deserialization straight from JSON text, without parsing it into
+//! `serde_json::Value` first. Both JSON and JSON-Verbose are accepted.
+//!
+//! Inputs handed to `TransitDeserialize` implementations point into the
+//! stream, so they must be consumed in order of appearance: an input left
+//! behind by advancing an iterator is skipped and cannot be used anymore.
+//!
+//! Strings without escape sequences are borrowed when reading from a slice.
+
+use super::handlers::ReadHandlers;
+use super::json::{JsonDeserializer, JsonMapIntoIter};
+use super::*;
+use crate::cache::{ReadCache, MAP_AS_ARRAY};
+use serde_json::{map::Map as JsMap, Number, Value as JsVal};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+use std::marker::PhantomData;
+
+/// Deserializes JSON or JSON-Verbose text read from `reader`
+pub fn from_reader<R: io::Read, T: TransitDeserializeOwned>(reader: R) -> TResult<T> {
+    let source = IoSource {
+        reader: BufReader::new(reader),
+    };
+    deserialize(source, None)
+}
+
+/// Same as `from_reader`, with handlers of custom tags
+pub fn from_reader_with<R: io::Read, T: TransitDeserializeOwned>(
+    reader: R,
+    handlers: &ReadHandlers,
+) -> TResult<T> {
+    let source = IoSource {
+        reader: BufReader::new(reader),
+    };
+    deserialize(source, Some(handlers))
+}
+
+pub fn from_slice<'de, T: TransitDeserialize<'de>>(v: &'de [u8]) -> TResult<T> {
+    deserialize(SliceSource { slice: v, pos: 0 }, None)
+}
+
+/// Same as `from_slice`, with handlers of custom tags
+pub fn from_slice_with<'de, T: TransitDeserialize<'de>>(
+    v: &'de [u8],
+    handlers: &ReadHandlers,
+) -> TResult<T> {
+    deserialize(SliceSource { slice: v, pos: 0 }, Some(handlers))
+}
+
+pub fn from_str<'de, T: TransitDeserialize<'de>>(v: &'de str) -> TResult<T> {
+    from_slice(v.as_bytes())
+}
+
+fn deserialize<'de, S: Source<'de>, T: TransitDeserialize<'de>>(
+    source: S,
+    handlers: Option<&ReadHandlers>,
+) -> TResult<T> {
+    let parser = RefCell::new(Parser::new(source));
+    let v = deserialize_next(&parser, handlers)?;
+    let rest = parser.borrow_mut().peek_ws()?;
+    match rest {
+        None => Ok(v),
+        Some(_) => Err(parse_error("trailing characters after value")),
+    }
+}
+
+fn deserialize_next<'de, S: Source<'de>, T: TransitDeserialize<'de>>(
+    parser: &RefCell<Parser<'de, S>>,
+    handlers: Option<&ReadHandlers>,
+) -> TResult<T> {
+    let input = parser.borrow_mut().begin_top_level()?;
+    let deserializer = ReadDeserializer { parser, handlers };
+    let v = TransitDeserialize::transit_deserialize(deserializer, input)?;
+    parser.borrow_mut().end_top_level()?;
+    Ok(v)
+}
+
+/// Iterates over a sequence of top-level values, each of them written with
+/// its own cache. Values may be concatenated or separated by whitespace,
+/// e.g. one value per line. Iteration stops after the first error.
+pub struct StreamDeserializer<R, T> {
+    parser: RefCell<Parser<'static, IoSource<R>>>,
+    handlers: Option<ReadHandlers>,
+    failed: bool,
+    marker: PhantomData<T>,
+}
+
+impl<R: io::Read, T: TransitDeserializeOwned> StreamDeserializer<R, T> {
+    pub fn new(reader: R) -> Self {
+        StreamDeserializer {
+            parser: RefCell::new(Parser::new(IoSource {
+                reader: BufReader::new(reader),
+            })),
+            handlers: None,
+            failed: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// Reader decoding custom tags of every value with given handlers
+    pub fn with_handlers(reader: R, handlers: ReadHandlers) -> Self {
+        StreamDeserializer {
+            handlers: Some(handlers),
+            ..Self::new(reader)
+        }
+    }
+}
+
+impl<R: io::Read, T: TransitDeserializeOwned> Iterator for StreamDeserializer<R, T> {
+    type Item = TResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.parser.borrow_mut().peek_ws();
+        let result = match next {
+            Ok(None) => return None,
+            Ok(Some(_)) => deserialize_next(&self.parser, self.handlers.as_ref()),
+            Err(e) => Err(e),
+        };
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Byte source for the parser
+trait Source<'de> {
+    fn peek(&mut self) -> TResult<Option<u8>>;
+    fn discard(&mut self);
+
+    /// Position of the next byte, only sources which can lend their bytes
+    /// keep track of it
+    fn position(&self) -> usize {
+        0
+    }
+
+    /// Bytes between two positions if they outlive the parser
+    fn borrow(&self, _start: usize, _end: usize) -> Option<&'de [u8]> {
+        None
+    }
+
+    fn next(&mut self) -> TResult<Option<u8>> {
+        let b = self.peek()?;
+        if b.is_some() {
+            self.discard();
+        }
+        Ok(b)
+    }
+}
+
+struct IoSource<R> {
+    reader: BufReader<R>,
+}
+
+impl<R: io::Read> Source<'_> for IoSource<R> {
+    fn peek(&mut self) -> TResult<Option<u8>> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok(buf) => return Ok(buf.first().cloned()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
+    }
+
+    fn discard(&mut self) {
+        self.reader.consume(1);
+    }
+}
+
+struct SliceSource<'a> {
+    slice: &'a [u8],
+    pos: usize,
+}
+
+impl<'de> Source<'de> for SliceSource<'de> {
+    fn peek(&mut self) -> TResult<Option<u8>> {
+        Ok(self.slice.get(self.pos).cloned())
+    }
+
+    fn discard(&mut self) {
+        self.pos += 1;
+    }
+
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn borrow(&self, start: usize, end: usize) -> Option<&'de [u8]> {
+        self.slice.get(start..end)
+    }
+}
+
+/// Beginning of an array or an object. To find out whether an array is a map
+/// or a tagged value its first item is read in advance if it is a string; for
+/// an object the first key is read.
+struct Header<'de> {
+    object: bool,
+    first: Option<Cow<'de, str>>,
+    closed: bool,
+}
+
+/// Beginning of a pending input which was read ahead to find out its kind
+enum Peeked<'de> {
+    Header(Header<'de>),
+    Value(JsVal),
+}
+
+/// Array or object which is being iterated over
+struct Frame {
+    id: u64,
+    object: bool,
+    map_keys: bool,
+    started: bool,
+    expect_value: bool,
+    count: usize,
+}
+
+impl Frame {
+    fn new(id: u64, header: &Header<'_>) -> Self {
+        Frame {
+            id,
+            object: header.object,
+            map_keys: !header.object && header.first.as_deref() == Some(MAP_AS_ARRAY),
+            started: header.first.is_some(),
+            expect_value: header.object && header.first.is_some(),
+            count: header.first.is_some() as usize,
+        }
+    }
+}
+
+/// Containers nested deeper than this are rejected instead of exhausting
+/// the stack, same limit as `serde_json` has
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'de, S> {
+    source: S,
+    cache: ReadCache<'de>,
+    next_id: u64,
+    pending: Option<u64>,
+    peeked: Option<(u64, Peeked<'de>)>,
+    frames: Vec<Frame>,
+    /// Containers being parsed completely, on top of `frames`
+    nesting: usize,
+}
+
+fn parse_error(msg: &str) -> Error {
+    Error::CannotParse(msg.to_owned())
+}
+
+impl<'de, S: Source<'de>> Parser<'de, S> {
+    fn new(source: S) -> Self {
+        Parser {
+            source,
+            cache: ReadCache::default(),
+            next_id: 0,
+            pending: None,
+            peeked: None,
+            frames: Vec::new(),
+            nesting: 0,
+        }
+    }
+
+    fn new_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn peek_ws(&mut self) -> TResult<Option<u8>> {
+        loop {
+            match self.source.peek()? {
+                Some(b' ') | Some(b'\n') | Some(b'\r') | Some(b'\t') => self.source.discard(),
+                b => return Ok(b),
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> TResult<()> {
+        match self.peek_ws()? {
+            Some(b) if b == expected => {
+                self.source.discard();
+                Ok(())
+            }
+            Some(b) => Err(Error::CannotParse(format!(
+                "expected {:?}, found {:?}",
+                expected as char, b as char
+            ))),
+            None => Err(parse_error("unexpected end of input")),
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &[u8]) -> TResult<()> {
+        for expected in literal {
+            if self.source.next()? != Some(*expected) {
+                return Err(parse_error("invalid literal"));
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_hex4(&mut self) -> TResult<u16> {
+        let mut n = 0;
+        for _ in 0..4 {
+            let digit = self
+                .source
+                .next()?
+                .and_then(|b| (b as char).to_digit(16))
+                .ok_or_else(|| parse_error("invalid unicode escape"))?;
+            n = n * 16 + digit as u16;
+        }
+        Ok(n)
+    }
+
+    fn parse_escape(&mut self, buf: &mut Vec<u8>) -> TResult<()> {
+        match self.source.next()? {
+            Some(b'"') => buf.push(b'"'),
+            Some(b'\\') => buf.push(b'\\'),
+            Some(b'/') => buf.push(b'/'),
+            Some(b'b') => buf.push(b'\x08'),
+            Some(b'f') => buf.push(b'\x0c'),
+            Some(b'n') => buf.push(b'\n'),
+            Some(b'r') => buf.push(b'\r'),
+            Some(b't') => buf.push(b'\t'),
+            Some(b'u') => {
+                let mut units = vec![self.parse_hex4()?];
+                if (0xD800..0xDC00).contains(&units[0]) {
+                    self.expect_literal(b"\\u")?;
+                    units.push(self.parse_hex4()?);
+                }
+                let c = std::char::decode_utf16(units)
+                    .next()
+                    .and_then(|c| c.ok())
+                    .ok_or_else(|| parse_error("invalid unicode escape"))?;
+                buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            _ => return Err(parse_error("invalid escape")),
+        }
+        Ok(())
+    }
+
+    /// Borrows the string from input unless it has escape sequences
+    fn parse_raw_string(&mut self) -> TResult<Cow<'de, str>> {
+        self.expect(b'"')?;
+        let start = self.source.position();
+        let mut buf = match self.source.borrow(start, start) {
+            Some(_) => None,
+            None => Some(Vec::new()),
+        };
+        loop {
+            match self.source.next()? {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    let end = self.source.position() - 1;
+                    let source = &self.source;
+                    let mut owned = buf.take().unwrap_or_else(|| {
+                        source
+                            .borrow(start, end)
+                            .map(<[u8]>::to_vec)
+                            .unwrap_or_default()
+                    });
+                    self.parse_escape(&mut owned)?;
+                    buf = Some(owned);
+                }
+                Some(b) if b < 0x20 => return Err(parse_error("control character in string")),
+                Some(b) => {
+                    if let Some(buf) = buf.as_mut() {
+                        buf.push(b);
+                    }
+                }
+                None => return Err(parse_error("unexpected end of string")),
+            }
+        }
+        let s = match buf {
+            Some(buf) => String::from_utf8(buf).ok().map(Cow::Owned),
+            None => {
+                let end = self.source.position() - 1;
+                let bytes = self.source.borrow(start, end).unwrap_or_default();
+                std::str::from_utf8(bytes).ok().map(Cow::Borrowed)
+            }
+        };
+        s.ok_or_else(|| parse_error("string is not valid UTF-8"))
+    }
+
+    fn read_str(&mut self, as_map_key: bool) -> TResult<Cow<'de, str>> {
+        let s = self.parse_raw_string()?;
+        let resolved = self.cache.cache_read(s, as_map_key);
+        resolved.ok_or_else(|| parse_error("cache code is not in cache"))
+    }
+
+    /// Appends digits to `s`, returns how many there were
+    fn take_digits(&mut self, s: &mut String) -> TResult<usize> {
+        let mut n = 0;
+        while let Some(b @ b'0'..=b'9') = self.source.peek()? {
+            s.push(b as char);
+            self.source.discard();
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    /// Appends the next byte to `s` if it is one of `bytes`
+    fn take_one_of(&mut self, s: &mut String, bytes: &[u8]) -> TResult<bool> {
+        match self.source.peek()? {
+            Some(b) if bytes.contains(&b) => {
+                s.push(b as char);
+                self.source.discard();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Follows JSON grammar: no leading `+` or zeros, digits on both sides
+    /// of `.` and after `e`
+    fn parse_number(&mut self) -> TResult<JsVal> {
+        let mut s = String::new();
+        let invalid = |s: &str| Error::CannotParse(format!("{:?} is not a number", s));
+        self.take_one_of(&mut s, b"-")?;
+        if !self.take_one_of(&mut s, b"0")? && self.take_digits(&mut s)? == 0 {
+            return Err(invalid(&s));
+        }
+        let mut is_float = false;
+        if self.take_one_of(&mut s, b".")? {
+            is_float = true;
+            if self.take_digits(&mut s)? == 0 {
+                return Err(invalid(&s));
+            }
+        }
+        if self.take_one_of(&mut s, b"eE")? {
+            is_float = true;
+            self.take_one_of(&mut s, b"+-")?;
+            if self.take_digits(&mut s)? == 0 {
+                return Err(invalid(&s));
+            }
+        }
+        let n = if is_float {
+            s.parse().ok().and_then(Number::from_f64)
+        } else {
+            s.parse::<i64>()
+                .map(Number::from)
+                .or_else(|_| s.parse::<u64>().map(Number::from))
+                .ok()
+                .or_else(|| s.parse().ok().and_then(Number::from_f64))
+        };
+        n.map(JsVal::Number).ok_or_else(|| invalid(&s))
+    }
+
+    /// Reads the next value completely
+    fn parse_value(&mut self) -> TResult<JsVal> {
+        match self.peek_ws()? {
+            Some(b'[') | Some(b'{') => {
+                let header = self.begin()?;
+                self.parse_rest(header)
+            }
+            Some(b'"') => self.read_str(false).map(|s| JsVal::String(s.into_owned())),
+            Some(b't') => self.expect_literal(b"true").map(|_| JsVal::Bool(true)),
+            Some(b'f') => self.expect_literal(b"false").map(|_| JsVal::Bool(false)),
+            Some(b'n') => self.expect_literal(b"null").map(|_| JsVal::Null),
+            Some(_) => self.parse_number(),
+            None => Err(parse_error("unexpected end of input")),
+        }
+    }
+
+    fn parse_rest(&mut self, header: Header<'de>) -> TResult<JsVal> {
+        self.nesting += 1;
+        let v = self.parse_items(header);
+        self.nesting -= 1;
+        v
+    }
+
+    fn parse_items(&mut self, header: Header<'de>) -> TResult<JsVal> {
+        if header.object {
+            let mut m = JsMap::new();
+            if let Some(k) = header.first {
+                m.insert(k.into_owned(), self.parse_value()?);
+            }
+            if !header.closed {
+                while self.next_item(m.is_empty(), b'}')? {
+                    let k = self.read_str(true)?.into_owned();
+                    self.expect(b':')?;
+                    m.insert(k, self.parse_value()?);
+                }
+            }
+            Ok(JsVal::Object(m))
+        } else {
+            let map_keys = header.first.as_deref() == Some(MAP_AS_ARRAY);
+            let mut vec: Vec<JsVal> = header
+                .first
+                .into_iter()
+                .map(|s| JsVal::String(s.into_owned()))
+                .collect();
+            if !header.closed {
+                while self.next_item(vec.is_empty(), b']')? {
+                    if map_keys && vec.len() % 2 == 1 {
+                        vec.push(JsVal::String(self.read_str(true)?.into_owned()));
+                    } else {
+                        vec.push(self.parse_value()?);
+                    }
+                }
+            }
+            Ok(JsVal::Array(vec))
+        }
+    }
+
+    /// Moves to the next item of a container, returns `false` at its end
+    fn next_item(&mut self, first: bool, end: u8) -> TResult<bool> {
+        match self.peek_ws()? {
+            Some(b) if b == end => {
+                self.source.discard();
+                Ok(false)
+            }
+            _ if first => Ok(true),
+            Some(b',') => {
+                self.source.discard();
+                Ok(true)
+            }
+            Some(_) => Err(parse_error("expected ',' between items")),
+            None => Err(parse_error("unexpected end of input")),
+        }
+    }
+
+    fn begin(&mut self) -> TResult<Header<'de>> {
+        if self.frames.len() + self.nesting >= MAX_DEPTH {
+            return Err(parse_error("recursion limit exceeded"));
+        }
+        match self.peek_ws()? {
+            Some(b'[') => {
+                self.source.discard();
+                let (first, closed) = match self.peek_ws()? {
+                    Some(b']') => {
+                        self.source.discard();
+                        (None, true)
+                    }
+                    Some(b'"') => (Some(self.read_str(false)?), false),
+                    _ => (None, false),
+                };
+                Ok(Header {
+                    object: false,
+                    first,
+                    closed,
+                })
+            }
+            Some(b'{') => {
+                self.source.discard();
+                let (first, closed) = match self.peek_ws()? {
+                    Some(b'}') => {
+                        self.source.discard();
+                        (None, true)
+                    }
+                    _ => {
+                        let k = self.read_str(true)?;
+                        self.expect(b':')?;
+                        (Some(k), false)
+                    }
+                };
+                Ok(Header {
+                    object: true,
+                    first,
+                    closed,
+                })
+            }
+            _ => Err(parse_error("expected array or object")),
+        }
+    }
+
+    /// Hands out the next value as an input, unwrapping quoted scalars
+    fn begin_top_level(&mut self) -> TResult<ReadInput<'de>> {
+        self.cache = ReadCache::default();
+        let id = self.new_id();
+        match self.peek_ws()? {
+            Some(b'[') | Some(b'{') => {
+                let header = self.begin()?;
+                let quote = matches!(
+                    (header.object, header.first.as_deref()),
+                    (_, Some("~#'")) | (true, Some("~#"))
+                );
+                if quote {
+                    self.frames.push(Frame::new(id, &header));
+                    self.next_value(id)?;
+                    Ok(self.hand_out(id))
+                } else {
+                    self.pending = Some(id);
+                    self.peeked = Some((id, Peeked::Header(header)));
+                    Ok(ReadInput::Pending(id))
+                }
+            }
+            _ => {
+                self.pending = Some(id);
+                Ok(ReadInput::Pending(id))
+            }
+        }
+    }
+
+    /// Skips everything which was not consumed
+    fn end_top_level(&mut self) -> TResult<()> {
+        self.close_frames_above(0)?;
+        self.skip_pending()
+    }
+
+    /// Skips a pending input nobody has consumed
+    fn skip_pending(&mut self) -> TResult<()> {
+        let id = match self.pending {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        match self.claim(id)? {
+            Some(Peeked::Header(header)) => {
+                if !header.closed {
+                    self.frames.push(Frame::new(id, &header));
+                    self.close_top_frame()?;
+                }
+            }
+            Some(Peeked::Value(_)) => (),
+            None => {
+                self.parse_value()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the pending input along with anything read ahead of it
+    fn claim(&mut self, id: u64) -> TResult<Option<Peeked<'de>>> {
+        if self.pending != Some(id) {
+            return Err(Error::DoNotMatch(
+                "input was skipped, inputs must be consumed in order".to_owned(),
+            ));
+        }
+        self.pending = None;
+        match self.peeked.take() {
+            Some((peeked_id, peeked)) if peeked_id == id => Ok(Some(peeked)),
+            _ => Ok(None),
+        }
+    }
+
+    fn peek_kind(&mut self, id: u64) -> TResult<InputKind> {
+        let peeked = match self.claim(id)? {
+            Some(peeked) => peeked,
+            None => match self.peek_ws()? {
+                Some(b'"') => {
+                    self.pending = Some(id);
+                    return Ok(InputKind::String);
+                }
+                Some(b'[') | Some(b'{') => Peeked::Header(self.begin()?),
+                _ => Peeked::Value(self.parse_value()?),
+            },
+        };
+        let kind = match &peeked {
+            Peeked::Header(header) if header.object => InputKind::Map,
+            Peeked::Header(header) => match header.first.as_deref() {
+                Some(s) if s == MAP_AS_ARRAY || s.starts_with("~#") => InputKind::Map,
+                _ => InputKind::Array,
+            },
+            Peeked::Value(v) => JsonDeserializer::default().input_kind(v)?,
+        };
+        self.pending = Some(id);
+        self.peeked = Some((id, peeked));
+        Ok(kind)
+    }
+
+    /// Reads a pending input if it is a string, borrowing it when possible
+    fn take_str(&mut self, id: u64) -> TResult<Option<Cow<'de, str>>> {
+        if self.pending == Some(id) && self.peeked.is_none() && self.peek_ws()? == Some(b'"') {
+            self.claim(id)?;
+            self.read_str(false).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Turns an input into a parsed value
+    fn take_value(&mut self, input: ReadInput<'de>) -> TResult<JsVal> {
+        match input {
+            ReadInput::Pending(id) => match self.claim(id)? {
+                Some(Peeked::Header(header)) => self.parse_rest(header),
+                Some(Peeked::Value(v)) => Ok(v),
+                None => self.parse_value(),
+            },
+            ReadInput::Value(v) => Ok(v),
+            ReadInput::Str(s) => Ok(JsVal::String(s.into_owned())),
+            ReadInput::Failed(e) => Err(e),
+        }
+    }
+
+    /// Starts iteration over an input which is not parsed yet
+    fn take_header(&mut self, id: u64) -> TResult<Header<'de>> {
+        match self.claim(id)? {
+            Some(Peeked::Header(header)) => Ok(header),
+            Some(Peeked::Value(v)) => {
+                Err(Error::DoNotMatch(format!("{} is not an array or a map", v)))
+            }
+            None => self.begin(),
+        }
+    }
+
+    fn push_frame(&mut self, header: &Header<'de>) -> Option<u64> {
+        if header.closed {
+            None
+        } else {
+            let id = self.new_id();
+            self.frames.push(Frame::new(id, header));
+            Some(id)
+        }
+    }
+
+    /// Skips remaining items of a container on top of the stack
+    fn close_top_frame(&mut self) -> TResult<()> {
+        if self.pending.is_some() {
+            self.skip_pending()?;
+        } else if self.frames.last().unwrap().expect_value {
+            self.parse_value()?;
+        }
+        let frame = self.frames.pop().unwrap();
+        let (end, mut count) = if frame.object {
+            (b'}', 0)
+        } else {
+            (b']', frame.count)
+        };
+        let mut started = frame.started;
+        while self.next_item(!started, end)? {
+            started = true;
+            if frame.object {
+                self.read_str(true)?;
+                self.expect(b':')?;
+                self.parse_value()?;
+            } else if frame.map_keys && count % 2 == 1 {
+                self.read_str(true)?;
+            } else {
+                self.parse_value()?;
+            }
+            count += 1;
+        }
+        Ok(())
+    }
+
+    fn close_frames_above(&mut self, depth: usize) -> TResult<()> {
+        while self.frames.len() > depth {
+            self.close_top_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Prepares a container to give its next item: skips everything left
+    /// unconsumed and reads a separator. Returns `false` at the end.
+    fn next_value(&mut self, frame_id: u64) -> TResult<bool> {
+        let depth = self
+            .frames
+            .iter()
+            .position(|f| f.id == frame_id)
+            .ok_or_else(|| {
+                Error::DoNotMatch(
+                    "iterator was skipped, inputs must be consumed in order".to_owned(),
+                )
+            })?;
+        self.close_frames_above(depth + 1)?;
+        let frame = &self.frames[depth];
+        if frame.expect_value {
+            return Ok(true);
+        }
+        let (started, end) = (frame.started, if frame.object { b'}' } else { b']' });
+        self.skip_pending()?;
+        if self.next_item(!started, end)? {
+            self.frames[depth].started = true;
+            Ok(true)
+        } else {
+            self.frames.pop();
+            Ok(false)
+        }
+    }
+
+    fn hand_out(&mut self, frame_id: u64) -> ReadInput<'de> {
+        let id = self.new_id();
+        self.pending = Some(id);
+        if let Some(frame) = self.frames.iter_mut().find(|f| f.id == frame_id) {
+            frame.expect_value = false;
+            frame.count += 1;
+        }
+        ReadInput::Pending(id)
+    }
+
+    fn next_array_item(&mut self, frame_id: u64) -> TResult<Option<ReadInput<'de>>> {
+        if self.next_value(frame_id)? {
+            Ok(Some(self.hand_out(frame_id)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_map_pair(
+        &mut self,
+        frame_id: u64,
+        first_key: Option<Cow<'de, str>>,
+    ) -> TResult<Option<(ReadInput<'de>, ReadInput<'de>)>> {
+        if let Some(k) = first_key {
+            return Ok(Some((ReadInput::Str(k), self.hand_out(frame_id))));
+        }
+        if !self.next_value(frame_id)? {
+            return Ok(None);
+        }
+        let object = self.frames.last().map(|f| f.object) == Some(true);
+        let k = self.read_str(true)?;
+        if object {
+            self.expect(b':')?;
+        } else {
+            self.frames.last_mut().unwrap().count += 1;
+            if !self.next_item(false, b']')? {
+                return Err(parse_error("map has a key without value"));
+            }
+        }
+        Ok(Some((ReadInput::Str(k), self.hand_out(frame_id))))
+    }
+}
+
+/// Position in the stream or an already parsed value
+#[derive(Debug)]
+enum ReadInput<'de> {
+    Pending(u64),
+    Value(JsVal),
+    Str(Cow<'de, str>),
+    Failed(Error),
+}
+
+struct ReadDeserializer<'p, 'de, S> {
+    parser: &'p RefCell<Parser<'de, S>>,
+    handlers: Option<&'p ReadHandlers>,
+}
+
+impl<S> Clone for ReadDeserializer<'_, '_, S> {
+    fn clone(&self) -> Self {
+        ReadDeserializer {
+            parser: self.parser,
+            handlers: self.handlers,
+        }
+    }
+}
+
+impl<S> fmt::Debug for ReadDeserializer<'_, '_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ReadDeserializer")
+    }
+}
+
+impl<'de, S: Source<'de>> ReadDeserializer<'_, 'de, S> {
+    fn value(&self, v: ReadInput<'de>) -> TResult<JsVal> {
+        self.parser.borrow_mut().take_value(v)
+    }
+}
+
+enum ReadArrayIter<'p, 'de, S> {
+    Lazy {
+        parser: &'p RefCell<Parser<'de, S>>,
+        frame: Option<u64>,
+        first: Option<Cow<'de, str>>,
+    },
+    Parsed(std::vec::IntoIter<JsVal>),
+}
+
+impl<'de, S: Source<'de>> Iterator for ReadArrayIter<'_, 'de, S> {
+    type Item = ReadInput<'de>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ReadArrayIter::Parsed(iter) => iter.next().map(ReadInput::Value),
+            ReadArrayIter::Lazy {
+                parser,
+                frame,
+                first,
+            } => {
+                if let Some(s) = first.take() {
+                    return Some(ReadInput::Str(s));
+                }
+                let frame_id = (*frame)?;
+                match parser.borrow_mut().next_array_item(frame_id) {
+                    Ok(Some(x)) => Some(x),
+                    Ok(None) => {
+                        *frame = None;
+                        None
+                    }
+                    Err(e) => {
+                        *frame = None;
+                        Some(ReadInput::Failed(e))
+                    }
+                }
+            }
+        }
+    }
+}
+
+enum ReadMapIter<'p, 'de, S> {
+    Lazy {
+        parser: &'p RefCell<Parser<'de, S>>,
+        frame: Option<u64>,
+        first_key: Option<Cow<'de, str>>,
+    },
+    Parsed(JsonMapIntoIter),
+}
+
+impl<'de, S: Source<'de>> Iterator for ReadMapIter<'_, 'de, S> {
+    type Item = (ReadInput<'de>, ReadInput<'de>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ReadMapIter::Parsed(iter) => iter
+                .next()
+                .map(|(k, v)| (ReadInput::Value(k), ReadInput::Value(v))),
+            ReadMapIter::Lazy {
+                parser,
+                frame,
+                first_key,
+            } => {
+                let frame_id = (*frame)?;
+                match parser
+                    .borrow_mut()
+                    .next_map_pair(frame_id, first_key.take())
+                {
+                    Ok(Some(x)) => Some(x),
+                    Ok(None) => {
+                        *frame = None;
+                        None
+                    }
+                    Err(e) => {
+                        *frame = None;
+                        Some((ReadInput::Failed(e), ReadInput::Value(JsVal::Null)))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'p, 'de, S: Source<'de>> TransitDeserializer<'de> for ReadDeserializer<'p, 'de, S> {
+    type Input = ReadInput<'de>;
+    type DeserializeArray = ReadArrayIter<'p, 'de, S>;
+    type DeserializeMap = ReadMapIter<'p, 'de, S>;
+
+    fn deserialize_string(self, v: Self::Input) -> TResult<String> {
+        match v {
+            ReadInput::Str(s) => Ok(s.into_owned()),
+            v => JsonDeserializer::default().deserialize_string(self.value(v)?),
+        }
+    }
+
+    fn deserialize_str(self, v: Self::Input) -> TResult<Cow<'de, str>> {
+        match v {
+            ReadInput::Str(s) => Ok(s),
+            ReadInput::Pending(id) => {
+                let s = self.parser.borrow_mut().take_str(id)?;
+                match s {
+                    Some(s) => Ok(s),
+                    None => self.deserialize_string(v).map(Cow::Owned),
+                }
+            }
+            v => self.deserialize_string(v).map(Cow::Owned),
+        }
+    }
+
+    fn deserialize_bool(self, v: Self::Input) -> TResult<bool> {
+        JsonDeserializer::default().deserialize_bool(self.value(v)?)
+    }
+
+    fn deserialize_int(self, v: Self::Input) -> TResult<i64> {
+        JsonDeserializer::default().deserialize_int(self.value(v)?)
+    }
+
+    fn deserialize_float(self, v: Self::Input) -> TResult<f64> {
+        JsonDeserializer::default().deserialize_float(self.value(v)?)
+    }
+
+    fn deserialize_array(self, v: Self::Input) -> TResult<(Self::DeserializeArray, Option<usize>)> {
+        match v {
+            ReadInput::Pending(id) => {
+                let mut parser = self.parser.borrow_mut();
+                let header = parser.take_header(id)?;
+                let is_array = !header.object
+                    && header
+                        .first
+                        .as_deref()
+                        .is_none_or(|s| s != MAP_AS_ARRAY && !s.starts_with("~#"));
+                if !is_array {
+                    return Err(Error::DoNotMatch("input is not an array".to_owned()));
+                }
+                let frame = parser.push_frame(&header);
+                Ok((
+                    ReadArrayIter::Lazy {
+                        parser: self.parser,
+                        frame,
+                        first: header.first,
+                    },
+                    None,
+                ))
+            }
+            v => {
+                let (iter, len) = JsonDeserializer::default().deserialize_array(self.value(v)?)?;
+                Ok((ReadArrayIter::Parsed(iter), len))
+            }
+        }
+    }
+
+    fn input_kind(&self, v: &Self::Input) -> TResult<InputKind> {
+        match v {
+            ReadInput::Pending(id) => self.parser.borrow_mut().peek_kind(*id),
+            ReadInput::Value(v) => JsonDeserializer::default().input_kind(v),
+            ReadInput::Str(_) => Ok(InputKind::String),
+            ReadInput::Failed(e) => Err(Error::DoNotMatch(format!("{:?}", e))),
+        }
+    }
+
+    fn read_handlers(&self) -> Option<&ReadHandlers> {
+        self.handlers
+    }
+
+    /// Tagged values are read as a map with a single entry
+    fn deserialize_map(self, v: Self::Input) -> TResult<(Self::DeserializeMap, Option<usize>)> {
+        match v {
+            ReadInput::Pending(id) => {
+                let mut parser = self.parser.borrow_mut();
+                let header = parser.take_header(id)?;
+                let first_key = match header.first.as_deref() {
+                    _ if header.object => header.first.clone(),
+                    Some(MAP_AS_ARRAY) => None,
+                    Some(tag) if tag.starts_with("~#") => header.first.clone(),
+                    _ => return Err(Error::DoNotMatch("input is not a map".to_owned())),
+                };
+                let frame = parser.push_frame(&header);
+                if let (Some(frame_id), false, Some(_)) = (frame, header.object, &first_key) {
+                    // value of a tag follows the tag after a comma
+                    if !parser.next_value(frame_id)? {
+                        return Err(parse_error("tag without value"));
+                    }
+                }
+                Ok((
+                    ReadMapIter::Lazy {
+                        parser: self.parser,
+                        frame,
+                        first_key,
+                    },
+                    None,
+                ))
+            }
+            v => {
+                let (iter, len) = JsonDeserializer::default().deserialize_map(self.value(v)?)?;
+                Ok((ReadMapIter::Parsed(iter), len))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ser::stream::{to_string, to_vec, Mode, StreamWriter};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn scalars() {
+        assert_eq!(5, from_str::<i32>(r#"["~#'", 5]"#).unwrap());
+        assert_eq!(5, from_str::<i32>(r#"{"~#'": 5}"#).unwrap());
+        assert_eq!(5, from_str::<i32>(" 5 ").unwrap());
+        assert_eq!(
+            "~a\"\u{1F600}",
+            from_str::<String>(r#"["~#'", "~~a\"\ud83d\ude00"]"#).unwrap()
+        );
+        assert!(from_str::<i32>("5 6").is_err());
+    }
+
+    #[test]
+    fn numbers() {
+        let tr: Vec<f64> = from_str("[0, -0, 1.5, -2e3, 1E+2, 0.5e-1]").unwrap();
+        assert_eq!(vec![0.0, 0.0, 1.5, -2e3, 1e2, 0.05], tr);
+        for s in &["01", "+1", "-", ".5", "1.", "1e", "1.e5", "-01", "1e+"] {
+            assert!(from_str::<f64>(s).is_err(), "{} was accepted", s);
+        }
+        assert!(from_str::<Vec<i64>>("[01]").is_err());
+    }
+
+    #[test]
+    fn deep_nesting() {
+        use crate::value::TransitValue;
+
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(from_str::<TransitValue>(&nested(100)).is_ok());
+        let deep = nested(100_000);
+        for tr in [
+            from_str::<TransitValue>(&deep),
+            from_reader(deep.as_bytes()),
+            from_str::<TransitValue>(&format!(r#"["~#'", {}]"#, deep)),
+        ] {
+            match tr {
+                Err(Error::CannotParse(_)) => (),
+                tr => panic!("{:?}", tr.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn arrays() {
+        let tr: Vec<Vec<String>> = from_str(r#"[["a", "b"], [], ["c"]]"#).unwrap();
+        assert_eq!(
+            vec![
+                vec!["a".to_owned(), "b".to_owned()],
+                vec![],
+                vec!["c".to_owned()]
+            ],
+            tr
+        );
+        assert!(from_str::<Vec<i32>>(r#"["^ ", "~i1", 2]"#).is_err());
+        assert!(from_str::<Vec<i32>>("[1, 2").is_err());
+    }
+
+    #[test]
+    fn maps() {
+        let mut m = BTreeMap::new();
+        m.insert(4, "yolo".to_owned());
+        m.insert(-6, "swag".to_owned());
+
+        let tr = from_str(r#"{"~i4": "yolo", "~i-6": "swag"}"#).unwrap();
+        assert_eq!(m, tr);
+        let tr = from_str(r#"["^ ", "~i4", "yolo", "~i-6", "swag"]"#).unwrap();
+        assert_eq!(m, tr);
+    }
+
+    #[test]
+    fn read_cache() {
+        let tr: Vec<BTreeMap<String, BTreeSet<String>>> = from_str(
+            r#"[["^ ", "langs", ["~#set", ["rust", "clojure"]]],
+                ["^ ", "^0", ["^1", ["lisp"]]]]"#,
+        )
+        .unwrap();
+        assert_eq!(2, tr.len());
+        assert_eq!(
+            Some(&vec!["lisp".to_owned()].into_iter().collect()),
+            tr[1].get("langs")
+        );
+    }
+
+    #[test]
+    fn skipped_values_fill_cache() {
+        struct Second(BTreeMap<String, i32>);
+
+        impl<'de> TransitDeserialize<'de> for Second {
+            const TF_TYPE: TransitType = TransitType::Composite;
+
+            fn transit_deserialize<D: TransitDeserializer<'de>>(
+                deserializer: D,
+                input: D::Input,
+            ) -> TResult<Self> {
+                let mut iter = deserializer.clone().deserialize_array(input)?.0;
+                iter.next();
+                let second = iter.next().unwrap();
+                TransitDeserialize::transit_deserialize(deserializer, second).map(Second)
+            }
+
+            fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+                _deserializer: D,
+                _input: D::Input,
+            ) -> TResult<Self> {
+                Err(Error::CannotBeKey("Second cannot be deserialized as key"))
+            }
+        }
+
+        let tr: Second =
+            from_str(r#"[["^ ", "skipped", 1], ["^ ", "^0", 2], ["ignored"]]"#).unwrap();
+        assert_eq!(Some(&2), tr.0.get("skipped"));
+    }
+
+    #[test]
+    fn borrowed() {
+        let input = r#"[["^ ", "~:name", "~~plain", "other", "esc\"aped"],
+                        ["^ ", "^0", "again", "^1", "x"]]"#;
+        let tr: Vec<BTreeMap<&str, Cow<'_, str>>> = from_str(input).unwrap();
+        assert_eq!(Some(&Cow::Borrowed("~plain")), tr[0].get("~:name"));
+        assert!(matches!(tr[0].get("other"), Some(Cow::Owned(_))));
+        assert_eq!(Some(&Cow::Borrowed("again")), tr[1].get("~:name"));
+
+        let tr: TResult<Vec<&str>> = from_str(r#"["esc\"aped"]"#);
+        assert!(tr.is_err());
+    }
+
+    #[test]
+    fn sequence() {
+        let tr: Vec<BTreeSet<String>> = StreamDeserializer::new(
+            r#"["~#set", ["a"]]["~#set", ["b"]]
+               {"~#set": []}"#
+                .as_bytes(),
+        )
+        .collect::<TResult<_>>()
+        .unwrap();
+        assert_eq!(3, tr.len());
+
+        let mut tr = StreamDeserializer::<_, Vec<i32>>::new("[1] [true] [2]".as_bytes());
+        assert_eq!(vec![1], tr.next().unwrap().unwrap());
+        assert!(tr.next().unwrap().is_err());
+        assert!(tr.next().is_none());
+    }
+
+    #[test]
+    fn sequence_roundtrip() {
+        let mut m: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        m.insert(
+            "langs".to_owned(),
+            vec!["~:rust".to_owned()].into_iter().collect(),
+        );
+
+        for mode in &[Mode::Json, Mode::JsonVerbose] {
+            let mut w = StreamWriter::new(Vec::new(), *mode);
+            for _ in 0..3 {
+                w.write(&m).unwrap();
+            }
+            let buf = w.into_inner();
+            let tr: Vec<BTreeMap<String, BTreeSet<String>>> = StreamDeserializer::new(&buf[..])
+                .collect::<TResult<_>>()
+                .unwrap();
+            assert_eq!(vec![m.clone(), m.clone(), m.clone()], tr);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut key1: BTreeMap<bool, String> = BTreeMap::new();
+        key1.insert(true, "test".to_owned());
+        key1.insert(false, "tset".to_owned());
+
+        let mut m: HashMap<BTreeMap<bool, String>, BTreeSet<String>> = HashMap::new();
+        m.insert(
+            key1,
+            vec!["~:keyword-like".to_owned(), "value".to_owned()]
+                .into_iter()
+                .collect(),
+        );
+        m.insert(BTreeMap::new(), BTreeSet::new());
+
+        for mode in &[Mode::Json, Mode::JsonVerbose] {
+            let tr = from_slice(&to_vec(&m, *mode).unwrap()).unwrap();
+            assert_eq!(m, tr);
+            let tr = from_reader(to_string(&m, *mode).unwrap().as_bytes()).unwrap();
+            assert_eq!(m, tr);
+        }
+    }
+}
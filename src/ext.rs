@@ -0,0 +1,13 @@
+//! Implementations for types of optional dependencies, each one behind
+//! a cargo feature
+
+#[cfg(feature = "bigint")]
+mod bigint;
+#[cfg(feature = "bytes")]
+mod bytes;
+#[cfg(any(feature = "rust_decimal", feature = "bigdecimal"))]
+mod decimal;
+#[cfg(feature = "url")]
+mod url;
+#[cfg(feature = "uuid")]
+mod uuid;
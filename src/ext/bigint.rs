@@ -0,0 +1,131 @@
+use crate::de::{Error, InputKind, TResult, TransitDeserialize, TransitDeserializer, TransitType};
+use crate::ser::{handlers, TransitKeySerializer, TransitSerialize, TransitSerializer};
+use num_bigint::{BigInt, BigUint};
+use std::str::FromStr;
+
+/// Decimal digits of an integer written as a number, `~n` or `~i` string
+fn digits<'de, D: TransitDeserializer<'de>>(deserializer: D, input: D::Input) -> TResult<String> {
+    if deserializer.input_kind(&input)? == InputKind::Int {
+        return deserializer.deserialize_int(input).map(|i| i.to_string());
+    }
+    let s = deserializer.deserialize_string(input)?;
+    match s.get(..2) {
+        Some("~n") | Some("~i") => Ok(s[2..].to_owned()),
+        _ => Err(Error::DoNotMatch(format!("{:?} is not big integer", s))),
+    }
+}
+
+fn parse<T: FromStr>(s: String) -> TResult<T> {
+    T::from_str(&s).map_err(|_| Error::CannotParse(format!("{:?} is not big integer", s)))
+}
+
+impl TransitSerialize for BigInt {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_string(&format!("~n{}", self)))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| Some(serializer.serialize_key(&format!("~n{}", self))))
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for BigInt {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        digits(deserializer, input).and_then(parse)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        digits(deserializer, input).and_then(parse)
+    }
+}
+
+impl TransitSerialize for BigUint {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_string(&format!("~n{}", self)))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| Some(serializer.serialize_key(&format!("~n{}", self))))
+    }
+}
+
+/// Negative numbers do not fit
+impl<'de> TransitDeserialize<'de> for BigUint {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let s = digits(deserializer, input)?;
+        if s.starts_with('-') {
+            return Err(Error::ItWontFit(format!("{} is negative", s)));
+        }
+        parse(s)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de, ser};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn values_and_keys() {
+        let big = BigInt::from_str("-123456789012345678901234567890").unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(big.clone(), vec![big]);
+
+        let tr = ser::json_verbose::to_transit_json(&m);
+        assert_eq!(
+            json!({"~n-123456789012345678901234567890": ["~n-123456789012345678901234567890"]}),
+            tr
+        );
+        let back: BTreeMap<BigInt, Vec<BigInt>> = de::json_verbose::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+
+        let mp = ser::msgpack::to_transit_msgpack(&m);
+        let back: BTreeMap<BigInt, Vec<BigInt>> = de::msgpack::from_transit_msgpack(&mp).unwrap();
+        assert_eq!(m, back);
+    }
+
+    #[test]
+    fn any_integer() {
+        let tr: Vec<BigUint> = de::json::from_transit_json(json!([1, "~i2", "~n3"])).unwrap();
+        assert_eq!(
+            vec![BigUint::from(1u8), BigUint::from(2u8), BigUint::from(3u8)],
+            tr
+        );
+        let tr: TResult<Vec<BigUint>> = de::json::from_transit_json(json!(["~n-3"]));
+        assert!(tr.is_err());
+        let tr: TResult<Vec<BigInt>> = de::json::from_transit_json(json!(["~n1.5", "~d1"]));
+        assert!(tr.is_err());
+    }
+}
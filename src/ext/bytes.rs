@@ -0,0 +1,57 @@
+use crate::de::{TResult, TransitDeserialize, TransitDeserializer, TransitType};
+use crate::ser::{handlers, TransitKeySerializer, TransitSerialize, TransitSerializer};
+use crate::types::{ByteBuf, Bytes};
+
+/// Falls back to a handler of `ByteBuf` when it has no handler of its own
+impl TransitSerialize for ::bytes::Bytes {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| Bytes(self).transit_serialize(serializer))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| Bytes(self).transit_serialize_key(serializer))
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for ::bytes::Bytes {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        deserializer.deserialize_bytes(input).map(Self::from)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        ByteBuf::transit_deserialize_key(deserializer, input).map(|b| Self::from(b.into_vec()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{de, ser};
+    use serde_json::json;
+
+    #[test]
+    fn roundtrip() {
+        let v = vec![::bytes::Bytes::from_static(b"transit")];
+        let tr = ser::json_verbose::to_transit_json(&v);
+        assert_eq!(json!(["~bdHJhbnNpdA=="]), tr);
+        let back: Vec<::bytes::Bytes> = de::json_verbose::from_transit_json(tr).unwrap();
+        assert_eq!(v, back);
+
+        let mp = ser::msgpack::to_transit_msgpack(&v);
+        assert_eq!(0xc4, mp[1], "native binary");
+        let back: Vec<::bytes::Bytes> = de::msgpack::from_transit_msgpack(&mp).unwrap();
+        assert_eq!(v, back);
+    }
+}
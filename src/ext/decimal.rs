@@ -0,0 +1,150 @@
+use crate::de::{Error, InputKind, TResult, TransitDeserialize, TransitDeserializer, TransitType};
+use crate::ser::{handlers, TransitKeySerializer, TransitSerialize, TransitSerializer};
+use std::str::FromStr;
+
+/// Digits of a decimal written as `~f` string, integers are accepted as
+/// well. Floats are not, they are not exact.
+fn digits<'de, D: TransitDeserializer<'de>>(deserializer: D, input: D::Input) -> TResult<String> {
+    if deserializer.input_kind(&input)? == InputKind::Int {
+        return deserializer.deserialize_int(input).map(|i| i.to_string());
+    }
+    let s = deserializer.deserialize_string(input)?;
+    match s.get(..2) {
+        Some("~f") | Some("~n") | Some("~i") => Ok(s[2..].to_owned()),
+        _ => Err(Error::DoNotMatch(format!("{:?} is not big decimal", s))),
+    }
+}
+
+fn parse<T: FromStr>(s: String) -> TResult<T> {
+    T::from_str(&s).map_err(|_| Error::CannotParse(format!("{:?} is not big decimal", s)))
+}
+
+#[cfg(feature = "rust_decimal")]
+impl TransitSerialize for rust_decimal::Decimal {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_string(&format!("~f{}", self)))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| Some(serializer.serialize_key(&format!("~f{}", self))))
+    }
+}
+
+/// Decimals in exponent notation are accepted too
+#[cfg(feature = "rust_decimal")]
+impl<'de> TransitDeserialize<'de> for rust_decimal::Decimal {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let s = digits(deserializer, input)?;
+        if s.contains(['e', 'E']) {
+            Self::from_scientific(&s)
+                .map_err(|_| Error::CannotParse(format!("{:?} is not big decimal", s)))
+        } else {
+            parse(s)
+        }
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl TransitSerialize for bigdecimal::BigDecimal {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_string(&format!("~f{}", self)))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| Some(serializer.serialize_key(&format!("~f{}", self))))
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl<'de> TransitDeserialize<'de> for bigdecimal::BigDecimal {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        digits(deserializer, input).and_then(parse)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        digits(deserializer, input).and_then(parse)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de, ser};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn rust_decimal() {
+        use rust_decimal::Decimal;
+
+        let price = Decimal::from_str("19.90").unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(price, vec![price]);
+
+        let tr = ser::json::to_transit_json(&m);
+        assert_eq!(json!(["^ ", "~f19.90", ["~f19.90"]]), tr);
+        let back: BTreeMap<Decimal, Vec<Decimal>> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+        assert_eq!("19.90", back.keys().next().unwrap().to_string());
+
+        let tr: Vec<Decimal> = de::json::from_transit_json(json!(["~f1.5E+2", 7])).unwrap();
+        assert_eq!(vec![Decimal::from(150), Decimal::from(7)], tr);
+        let tr: TResult<Vec<Decimal>> = de::json::from_transit_json(json!([1.5]));
+        assert!(tr.is_err());
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    #[test]
+    fn bigdecimal() {
+        use bigdecimal::BigDecimal;
+
+        let v = BigDecimal::from_str("12345678901234567890.000000000001").unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(v.clone(), vec![v]);
+
+        let tr = ser::json_verbose::to_transit_json(&m);
+        assert_eq!(
+            json!({"~f12345678901234567890.000000000001": ["~f12345678901234567890.000000000001"]}),
+            tr
+        );
+        let back: BTreeMap<BigDecimal, Vec<BigDecimal>> =
+            de::json_verbose::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+
+        let mp = ser::msgpack::to_transit_msgpack(&m);
+        let back: BTreeMap<BigDecimal, Vec<BigDecimal>> =
+            de::msgpack::from_transit_msgpack(&mp).unwrap();
+        assert_eq!(m, back);
+    }
+}
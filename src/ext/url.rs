@@ -0,0 +1,80 @@
+use crate::de::{Error, TResult, TransitDeserialize, TransitDeserializer, TransitType};
+use crate::ser::{handlers, TransitKeySerializer, TransitSerialize, TransitSerializer};
+use ::url::Url;
+
+impl TransitSerialize for Url {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_string(&format!("~r{}", self)))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| Some(serializer.serialize_key(&format!("~r{}", self))))
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for Url {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let s = deserializer.deserialize_string(input)?;
+        let url = s
+            .strip_prefix("~r")
+            .ok_or_else(|| Error::DoNotMatch(format!("{:?} is not Url", s)))?;
+        Url::parse(url).map_err(|e| Error::CannotParse(format!("{:?} is not Url: {}", url, e)))
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de, ser};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn values_and_keys() {
+        let url = Url::parse("https://example.com/users?page=2").unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(url.clone(), url);
+
+        let tr = ser::json::to_transit_json(&m);
+        assert_eq!(
+            json!([
+                "^ ",
+                "~rhttps://example.com/users?page=2",
+                "~rhttps://example.com/users?page=2"
+            ]),
+            tr
+        );
+        let back: BTreeMap<Url, Url> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+
+        let mp = ser::msgpack::to_transit_msgpack(&m);
+        let back: BTreeMap<Url, Url> = de::msgpack::from_transit_msgpack(&mp).unwrap();
+        assert_eq!(m, back);
+    }
+
+    #[test]
+    fn invalid() {
+        let err: TResult<Url> = de::json::from_transit_json(json!(["~#'", "~rnot a url"]));
+        match err {
+            Err(Error::CannotParse(_)) => (),
+            e => panic!("{:?} must be a parse error", e),
+        }
+    }
+}
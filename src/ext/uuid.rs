@@ -0,0 +1,125 @@
+use crate::de::{Error, TResult, TransitDeserialize, TransitDeserializer, TransitType};
+use crate::ser::{handlers, TransitKeySerializer, TransitSerialize, TransitSerializer};
+use crate::value::TransitValue;
+use ::uuid::Uuid;
+
+impl TransitSerialize for Uuid {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_string(&format!("~u{}", self.hyphenated())))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| Some(serializer.serialize_key(&format!("~u{}", self.hyphenated()))))
+    }
+}
+
+fn from_value(v: TransitValue) -> TResult<Uuid> {
+    match v {
+        TransitValue::Uuid(u) => Ok(Uuid::from_u128(u)),
+        v => Err(Error::DoNotMatch(format!("{:?} is not uuid", v))),
+    }
+}
+
+/// Reads both `~u` strings and `["~#u", [hi, lo]]` pairs of 64-bit ints
+impl<'de> TransitDeserialize<'de> for Uuid {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        from_value(TransitValue::transit_deserialize(deserializer, input)?)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        from_value(TransitValue::transit_deserialize_key(deserializer, input)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de, ser};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    const ID: &str = "5a2cbea3-e8c6-428b-b525-21239370dd55";
+
+    #[test]
+    fn values_and_keys() {
+        let id = Uuid::parse_str(ID).unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(id, vec![id]);
+
+        let tr = ser::json_verbose::to_transit_json(&m);
+        assert_eq!(json!({ format!("~u{}", ID): [format!("~u{}", ID)] }), tr);
+        let back: BTreeMap<Uuid, Vec<Uuid>> = de::json_verbose::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+
+        let tr = ser::json::to_transit_json(&m);
+        assert_eq!(
+            json!(["^ ", format!("~u{}", ID), [format!("~u{}", ID)]]),
+            tr
+        );
+        let back: BTreeMap<Uuid, Vec<Uuid>> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+
+        let mp = ser::msgpack::to_transit_msgpack(&m);
+        let back: BTreeMap<Uuid, Vec<Uuid>> = de::msgpack::from_transit_msgpack(&mp).unwrap();
+        assert_eq!(m, back);
+    }
+
+    #[test]
+    fn handler() {
+        struct PairHandler;
+
+        impl handlers::WriteHandler<Uuid> for PairHandler {
+            fn tag(&self, _v: &Uuid) -> String {
+                "u".to_owned()
+            }
+
+            fn rep(&self, v: &Uuid) -> TransitValue {
+                let (hi, lo) = v.as_u64_pair();
+                TransitValue::Array(vec![
+                    TransitValue::Int(hi as i64),
+                    TransitValue::Int(lo as i64),
+                ])
+            }
+        }
+
+        let id = Uuid::parse_str(ID).unwrap();
+        let mut h = handlers::WriteHandlers::new();
+        h.insert(PairHandler);
+        let tr = ser::json::to_transit_json_with(vec![id], &h);
+        let (hi, lo) = id.as_u64_pair();
+        assert_eq!(
+            json!([[
+                "~#u",
+                [format!("~i{}", hi as i64), format!("~i{}", lo as i64)]
+            ]]),
+            tr
+        );
+        let back: Vec<Uuid> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(vec![id], back);
+    }
+
+    #[test]
+    fn pair_of_ints() {
+        let id = Uuid::parse_str(ID).unwrap();
+        let (hi, lo) = id.as_u64_pair();
+        let tr: Vec<Uuid> =
+            de::json::from_transit_json(json!([["~#u", [hi as i64, lo as i64]]])).unwrap();
+        assert_eq!(vec![id], tr);
+
+        let err: TResult<Vec<Uuid>> = de::json::from_transit_json(json!(["~unot-a-uuid"]));
+        assert!(err.is_err());
+    }
+}
@@ -1,5 +1,23 @@
-// pub mod de;
+mod cache;
+pub mod de;
+mod ext;
 pub mod ser;
+pub mod types;
+pub mod value;
+
+/// Writes any `serde::Serialize` value as verbose Transit JSON
+pub fn to_transit_json_serde<T: serde::Serialize + ?Sized>(
+    v: &T,
+) -> Result<serde_json::Value, de::Error> {
+    ser::serde_bridge::to_transit_value(v).map(ser::json_verbose::to_transit_json)
+}
+
+/// Reads any `serde::Deserialize` value from verbose Transit JSON
+pub fn from_transit_json_serde<T: serde::de::DeserializeOwned>(
+    v: serde_json::Value,
+) -> Result<T, de::Error> {
+    de::json_verbose::from_transit_json_serde(v)
+}
 
 #[cfg(test)]
 mod tests {
@@ -1,6 +1,36 @@
+pub mod edn;
+pub mod handlers;
 mod impls;
+pub mod json;
 pub mod json_verbose;
-//pub mod json;
+pub mod msgpack;
+pub mod plain_json;
+pub mod serde_bridge;
+pub mod stream;
+mod tree;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+/// `~z` encoding of NaN and infinities, which JSON has no numbers for
+pub(crate) fn special_float(v: f64) -> Option<&'static str> {
+    if v.is_nan() {
+        Some("~zNaN")
+    } else if v == f64::INFINITY {
+        Some("~zINF")
+    } else if v == f64::NEG_INFINITY {
+        Some("~z-INF")
+    } else {
+        None
+    }
+}
+
+/// Ints beyond `±2^53` lose precision as JavaScript numbers, so JSON writers
+/// put them into `~i` strings
+pub(crate) const JSON_INT_MAX: u64 = 1 << 53;
+
+pub(crate) fn is_json_safe_int(v: i64) -> bool {
+    v.unsigned_abs() <= JSON_INT_MAX
+}
 
 pub trait TransitSerialize {
     fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output;
@@ -24,11 +54,27 @@ pub trait TransitSerializer {
     fn serialize_bool(&self, v: bool) -> Self::Output;
     fn serialize_int(&self, v: i64) -> Self::Output;
     fn serialize_float(&self, v: f64) -> Self::Output;
+    /// Written as `~b` base64 string unless the format has native binary
+    fn serialize_bytes(&self, v: &[u8]) -> Self::Output {
+        self.serialize_string(&format!("~b{}", BASE64.encode(v)))
+    }
 
     fn serialize_array(&self, len: Option<usize>) -> Self::ArraySerializer;
     fn serialize_map(&self, len: Option<usize>) -> Self::MapSerializer;
     fn serialize_tagged_array(&self, tag: &str, len: Option<usize>) -> Self::TaggedArraySerializer;
     fn serialize_tagged_map(&self, tag: &str, len: Option<usize>) -> Self::TaggedMapSerializer;
+    /// Handlers registered for this writer. Impls of `handlers::Handled`,
+    /// instants, `types::*` and optional crate types look for a handler of
+    /// their type here before using their own encoding.
+    fn write_handlers(&self) -> Option<&handlers::WriteHandlers> {
+        None
+    }
+    /// Verbose writers pick verbose handlers when there are any
+    fn is_verbose(&self) -> bool {
+        false
+    }
+    /// Tagged value with any representation, e.g. `["~#point", "1,2"]`
+    fn serialize_tagged<T: TransitSerialize>(&self, tag: &str, rep: &T) -> Self::Output;
 
     fn serialize_array_iter<'t, T, I>(&self, v: I) -> Self::Output
     where
@@ -57,6 +103,12 @@ pub trait TransitKeySerializer {
     type Output;
 
     fn serialize_key(&self, v: &str) -> Self::Output;
+    fn write_handlers(&self) -> Option<&handlers::WriteHandlers> {
+        None
+    }
+    fn is_verbose(&self) -> bool {
+        false
+    }
 }
 
 pub trait TransitArraySerializer {
@@ -0,0 +1,572 @@
+use super::*;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use std::fmt;
+
+/// Writes a value in EDN notation on a single line, it is read back by
+/// `de::edn::from_str`
+pub fn to_string<T: TransitSerialize>(v: &T) -> String {
+    let mut out = String::new();
+    v.transit_serialize(&EdnSerializer).write_flat(&mut out);
+    out
+}
+
+/// Writes a value in EDN notation breaking collections which do not fit
+/// into 80 columns
+pub fn to_string_pretty<T: TransitSerialize>(v: &T) -> String {
+    PrettyPrinter::default().to_string(v)
+}
+
+/// Configurable EDN pretty-printer. Collections which do not fit into
+/// `width` columns are written one item (or map entry) per line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrettyPrinter {
+    width: usize,
+    indent: usize,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        PrettyPrinter {
+            width: 80,
+            indent: 2,
+        }
+    }
+}
+
+impl PrettyPrinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn width(self, width: usize) -> Self {
+        PrettyPrinter { width, ..self }
+    }
+
+    pub fn indent(self, indent: usize) -> Self {
+        PrettyPrinter { indent, ..self }
+    }
+
+    pub fn to_string<T: TransitSerialize>(&self, v: &T) -> String {
+        let mut out = String::new();
+        v.transit_serialize(&EdnSerializer)
+            .write_pretty(&mut out, self, 0, 0);
+        out
+    }
+}
+
+/// Displays any `TransitSerialize` value as EDN, alternate flag (`{:#}`)
+/// enables pretty-printing with default settings
+pub struct Edn<'a, T: ?Sized>(pub &'a T);
+
+impl<T: TransitSerialize + ?Sized> fmt::Display for Edn<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str(&to_string_pretty(&self.0))
+        } else {
+            f.write_str(&to_string(&self.0))
+        }
+    }
+}
+
+/// Document tree which is laid out once its flat width is known
+#[derive(Debug)]
+enum Doc {
+    Atom(String),
+    Seq {
+        open: &'static str,
+        close: &'static str,
+        items: Vec<Doc>,
+    },
+    Map(Vec<(Doc, Doc)>),
+    Tagged(String, Box<Doc>),
+}
+
+impl Doc {
+    fn flat_len(&self) -> usize {
+        match self {
+            Doc::Atom(s) => s.chars().count(),
+            Doc::Seq { open, close, items } => {
+                open.len()
+                    + close.len()
+                    + items.iter().map(Doc::flat_len).sum::<usize>()
+                    + items.len().saturating_sub(1)
+            }
+            Doc::Map(pairs) => {
+                2 + pairs
+                    .iter()
+                    .map(|(k, v)| k.flat_len() + 1 + v.flat_len())
+                    .sum::<usize>()
+                    + 2 * pairs.len().saturating_sub(1)
+            }
+            Doc::Tagged(tag, rep) => tag.chars().count() + 2 + rep.flat_len(),
+        }
+    }
+
+    fn write_flat(&self, out: &mut String) {
+        match self {
+            Doc::Atom(s) => out.push_str(s),
+            Doc::Seq { open, close, items } => {
+                out.push_str(open);
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(' ');
+                    }
+                    item.write_flat(out);
+                }
+                out.push_str(close);
+            }
+            Doc::Map(pairs) => {
+                out.push('{');
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    k.write_flat(out);
+                    out.push(' ');
+                    v.write_flat(out);
+                }
+                out.push('}');
+            }
+            Doc::Tagged(tag, rep) => {
+                out.push('#');
+                out.push_str(tag);
+                out.push(' ');
+                rep.write_flat(out);
+            }
+        }
+    }
+
+    /// `column` is where the document starts, `level` is indentation
+    /// of the line it starts on
+    fn write_pretty(&self, out: &mut String, p: &PrettyPrinter, column: usize, level: usize) {
+        if column + self.flat_len() <= p.width {
+            return self.write_flat(out);
+        }
+        let newline = |out: &mut String, level: usize| {
+            out.push('\n');
+            out.extend(std::iter::repeat_n(' ', level));
+        };
+        let inner = level + p.indent;
+        match self {
+            Doc::Atom(_) => self.write_flat(out),
+            Doc::Seq { open, close, items } => {
+                out.push_str(open);
+                for item in items {
+                    newline(out, inner);
+                    item.write_pretty(out, p, inner, inner);
+                }
+                newline(out, level);
+                out.push_str(close);
+            }
+            Doc::Map(pairs) => {
+                out.push('{');
+                for (k, v) in pairs {
+                    newline(out, inner);
+                    let start = out.len();
+                    k.write_pretty(out, p, inner, inner);
+                    let key_end = match out.rfind('\n') {
+                        Some(i) if i >= start => out[i + 1..].chars().count(),
+                        _ => inner + out[start..].chars().count(),
+                    };
+                    out.push(' ');
+                    v.write_pretty(out, p, key_end + 1, inner);
+                }
+                newline(out, level);
+                out.push('}');
+            }
+            Doc::Tagged(tag, rep) => {
+                out.push('#');
+                out.push_str(tag);
+                out.push(' ');
+                rep.write_pretty(out, p, column + tag.chars().count() + 2, level);
+            }
+        }
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn char_literal(c: char) -> String {
+    match c {
+        '\n' => "\\newline".to_owned(),
+        ' ' => "\\space".to_owned(),
+        '\t' => "\\tab".to_owned(),
+        '\r' => "\\return".to_owned(),
+        '\u{8}' => "\\backspace".to_owned(),
+        '\u{c}' => "\\formfeed".to_owned(),
+        c => format!("\\{}", c),
+    }
+}
+
+fn float_literal(v: f64) -> String {
+    if v.is_nan() {
+        "##NaN".to_owned()
+    } else if v.is_infinite() {
+        if v > 0.0 { "##Inf" } else { "##-Inf" }.to_owned()
+    } else {
+        format!("{:?}", v)
+    }
+}
+
+fn tagged_string(tag: &str, rep: &str) -> Doc {
+    Doc::Tagged(tag.to_owned(), Box::new(Doc::Atom(string_literal(rep))))
+}
+
+/// Turns a Transit string, which may encode a scalar of any type, into
+/// its EDN literal. Invalid representations are kept as string literals.
+fn scalar(s: &str) -> Doc {
+    let mut chars = s.chars();
+    let tag = match (chars.next(), chars.next()) {
+        (Some('~'), Some(tag)) => tag,
+        _ => return Doc::Atom(string_literal(s)),
+    };
+    let rep = chars.as_str();
+    let invalid = || Doc::Atom(string_literal(s));
+    let atom = match tag {
+        '~' | '^' | '`' => string_literal(&s[1..]),
+        '_' => "nil".to_owned(),
+        '?' => match rep {
+            "t" => "true".to_owned(),
+            "f" => "false".to_owned(),
+            _ => return invalid(),
+        },
+        'i' => rep.to_owned(),
+        'n' => format!("{}N", rep),
+        'd' => match rep.parse() {
+            Ok(f) => float_literal(f),
+            Err(_) => return invalid(),
+        },
+        'f' => format!("{}M", rep),
+        'z' => match rep {
+            "INF" => "##Inf".to_owned(),
+            "-INF" => "##-Inf".to_owned(),
+            "NaN" => "##NaN".to_owned(),
+            _ => return invalid(),
+        },
+        ':' => format!(":{}", rep),
+        '$' => rep.to_owned(),
+        'c' => match rep.chars().next() {
+            Some(c) => char_literal(c),
+            None => return invalid(),
+        },
+        't' => return tagged_string("inst", rep),
+        'm' => match rep
+            .parse()
+            .ok()
+            .and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+        {
+            Some(t) => {
+                return tagged_string("inst", &t.to_rfc3339_opts(SecondsFormat::Millis, true))
+            }
+            None => return invalid(),
+        },
+        'u' => return tagged_string("uuid", rep),
+        'r' => return tagged_string("uri", rep),
+        'b' => return tagged_string("base64", rep),
+        tag => return tagged_string(&tag.to_string(), rep),
+    };
+    Doc::Atom(atom)
+}
+
+/// Renders the serialized value as EDN, so composite map keys need no
+/// special encoding and are written inline
+struct EdnSerializer;
+
+impl TransitSerializer for EdnSerializer {
+    type Output = Doc;
+    type ArraySerializer = EdnArraySerializer;
+    type MapSerializer = EdnMapSerializer;
+    type TaggedArraySerializer = EdnArraySerializer;
+    type TaggedMapSerializer = EdnMapSerializer;
+
+    fn serialize_null(&self) -> Self::Output {
+        Doc::Atom("nil".to_owned())
+    }
+
+    fn serialize_string(&self, v: &str) -> Self::Output {
+        scalar(v)
+    }
+
+    fn serialize_bool(&self, v: bool) -> Self::Output {
+        Doc::Atom(v.to_string())
+    }
+
+    fn serialize_int(&self, v: i64) -> Self::Output {
+        Doc::Atom(v.to_string())
+    }
+
+    fn serialize_float(&self, v: f64) -> Self::Output {
+        Doc::Atom(float_literal(v))
+    }
+
+    fn serialize_array(&self, len: Option<usize>) -> Self::ArraySerializer {
+        EdnArraySerializer::new(None, len)
+    }
+
+    fn serialize_map(&self, len: Option<usize>) -> Self::MapSerializer {
+        EdnMapSerializer::new(None, len)
+    }
+
+    fn serialize_tagged_array(&self, tag: &str, len: Option<usize>) -> Self::TaggedArraySerializer {
+        EdnArraySerializer::new(Some(tag), len)
+    }
+
+    fn serialize_tagged_map(&self, tag: &str, len: Option<usize>) -> Self::TaggedMapSerializer {
+        EdnMapSerializer::new(Some(tag), len)
+    }
+
+    fn serialize_tagged<T: TransitSerialize>(&self, tag: &str, rep: &T) -> Self::Output {
+        tagged(tag, rep.transit_serialize(self))
+    }
+
+    fn serialize_array_iter<'t, T, I>(&self, v: I) -> Self::Output
+    where
+        T: TransitSerialize + 't,
+        I: Iterator<Item = &'t T>,
+    {
+        let mut ser = self.serialize_array(None);
+        v.for_each(|x| TransitArraySerializer::serialize_item(&mut ser, x));
+        TransitArraySerializer::end(ser)
+    }
+
+    fn serialize_map_iter<'t, K, V, I>(&self, v: I) -> Self::Output
+    where
+        K: TransitSerialize + 't,
+        V: TransitSerialize + 't,
+        I: Iterator<Item = (&'t K, &'t V)>,
+    {
+        let mut ser = self.serialize_map(None);
+        v.for_each(|(k, v)| TransitMapSerializer::serialize_pair(&mut ser, k, v));
+        TransitMapSerializer::end(ser)
+    }
+
+    fn serialize_tagged_array_iter<'t, T, I>(&self, tag: &str, v: I) -> Self::Output
+    where
+        T: TransitSerialize + 't,
+        I: Iterator<Item = &'t T>,
+    {
+        let mut ser = self.serialize_tagged_array(tag, None);
+        v.for_each(|x| TransitTaggedArraySerializer::serialize_item(&mut ser, x));
+        TransitTaggedArraySerializer::end(ser)
+    }
+
+    fn serialize_tagged_map_iter<'t, K, V, I>(&self, tag: &str, v: I) -> Self::Output
+    where
+        K: TransitSerialize + 't,
+        V: TransitSerialize + 't,
+        I: Iterator<Item = (&'t K, &'t V)>,
+    {
+        let mut ser = self.serialize_tagged_map(tag, None);
+        v.for_each(|(k, v)| TransitTaggedMapSerializer::serialize_pair(&mut ser, k, v));
+        TransitTaggedMapSerializer::end(ser)
+    }
+}
+
+impl TransitKeySerializer for EdnSerializer {
+    type Output = Doc;
+
+    fn serialize_key(&self, v: &str) -> Self::Output {
+        scalar(v)
+    }
+}
+
+/// Wraps a representation into an EDN tagged literal, `~#'` quotes are
+/// dropped as EDN does not need them
+fn tagged(tag: &str, rep: Doc) -> Doc {
+    match tag.trim_start_matches("~#") {
+        "'" | "" => rep,
+        tag => Doc::Tagged(tag.to_owned(), Box::new(rep)),
+    }
+}
+
+struct EdnArraySerializer {
+    tag: Option<String>,
+    items: Vec<Doc>,
+}
+
+impl EdnArraySerializer {
+    fn new(tag: Option<&str>, len: Option<usize>) -> Self {
+        EdnArraySerializer {
+            tag: tag.map(ToOwned::to_owned),
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        }
+    }
+}
+
+impl TransitArraySerializer for EdnArraySerializer {
+    type Output = Doc;
+
+    fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
+        self.items.push(v.transit_serialize(&EdnSerializer));
+    }
+
+    /// Sets and lists have their own literals, other tags are written
+    /// in front of a vector
+    fn end(self) -> Self::Output {
+        let seq = |open, close, items| Doc::Seq { open, close, items };
+        match self.tag.as_deref() {
+            None => seq("[", "]", self.items),
+            Some("~#set") => seq("#{", "}", self.items),
+            Some("~#list") => seq("(", ")", self.items),
+            Some(tag) => tagged(tag, seq("[", "]", self.items)),
+        }
+    }
+}
+
+impl TransitTaggedArraySerializer for EdnArraySerializer {
+    type Output = Doc;
+
+    fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
+        TransitArraySerializer::serialize_item(self, v)
+    }
+
+    fn end(self) -> Self::Output {
+        TransitArraySerializer::end(self)
+    }
+}
+
+struct EdnMapSerializer {
+    tag: Option<String>,
+    pairs: Vec<(Doc, Doc)>,
+}
+
+impl EdnMapSerializer {
+    fn new(tag: Option<&str>, len: Option<usize>) -> Self {
+        EdnMapSerializer {
+            tag: tag.map(ToOwned::to_owned),
+            pairs: Vec::with_capacity(len.unwrap_or(0)),
+        }
+    }
+}
+
+impl TransitMapSerializer for EdnMapSerializer {
+    type Output = Doc;
+
+    fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: &K, v: &V) {
+        let k = k
+            .transit_serialize_key(&EdnSerializer)
+            .unwrap_or_else(|| k.transit_serialize(&EdnSerializer));
+        self.pairs.push((k, v.transit_serialize(&EdnSerializer)));
+    }
+
+    fn end(self) -> Self::Output {
+        match self.tag {
+            Some(tag) => tagged(&tag, Doc::Map(self.pairs)),
+            None => Doc::Map(self.pairs),
+        }
+    }
+}
+
+impl TransitTaggedMapSerializer for EdnMapSerializer {
+    type Output = Doc;
+
+    fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: &K, v: &V) {
+        TransitMapSerializer::serialize_pair(self, k, v)
+    }
+
+    fn end(self) -> Self::Output {
+        TransitMapSerializer::end(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::value::TransitValue;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn scalars() {
+        assert_eq!("nil", to_string(&None::<i32>));
+        assert_eq!(
+            "[1 -2.5 true]",
+            to_string(&vec![
+                TransitValue::Int(1),
+                TransitValue::Float(-2.5),
+                TransitValue::Bool(true)
+            ])
+        );
+        assert_eq!(r#""~tilde \"q\"\n""#, to_string(&"~tilde \"q\"\n"));
+        assert_eq!(
+            r#"#inst "2019-04-01T12:30:00.000Z""#,
+            to_string(&Utc.with_ymd_and_hms(2019, 4, 1, 12, 30, 0).unwrap())
+        );
+        assert_eq!(
+            r#"[:kw sym \λ \space 12N 1.5M ##NaN #uuid "00000000-0000-0000-0000-00000000002a"]"#,
+            to_string(&vec![
+                TransitValue::Keyword("kw".to_owned()),
+                TransitValue::Symbol("sym".to_owned()),
+                TransitValue::Char('λ'),
+                TransitValue::Char(' '),
+                TransitValue::BigInt("12".to_owned()),
+                TransitValue::BigDec("1.5".to_owned()),
+                TransitValue::Float(f64::NAN),
+                TransitValue::Uuid(42),
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_scalars() {
+        use crate::value::RawValue;
+
+        for s in &["~?x", "~c", "~mnope", "~zINFINITY", "~d1.2.3"] {
+            let v = RawValue::String(s.to_string());
+            assert_eq!(format!("{:?}", s), to_string(&v));
+        }
+    }
+
+    #[test]
+    fn collections() {
+        let set: BTreeSet<i32> = vec![1, 2].into_iter().collect();
+        assert_eq!("#{1 2}", to_string(&set));
+
+        let mut key = BTreeMap::new();
+        key.insert(true, "yes".to_owned());
+        let mut m = BTreeMap::new();
+        m.insert(key, set);
+        assert_eq!(r#"{{true "yes"} #{1 2}}"#, to_string(&m));
+
+        let point = TransitValue::Tagged(
+            "point".to_owned(),
+            Box::new(TransitValue::List(vec![TransitValue::Int(1)])),
+        );
+        assert_eq!("#point (1)", to_string(&point));
+    }
+
+    #[test]
+    fn pretty() {
+        let mut m = BTreeMap::new();
+        m.insert("numbers".to_owned(), (0..12).collect::<Vec<i32>>());
+        m.insert("empty".to_owned(), vec![]);
+        assert_eq!(
+            "{\"empty\" [], \"numbers\" [0 1 2 3 4 5 6 7 8 9 10 11]}",
+            to_string_pretty(&m)
+        );
+        assert_eq!(
+            "{\n  \"empty\" []\n  \"numbers\" [\n    0\n    1\n    2\n    3\n    4\n    5\n    6\n    7\n    8\n    9\n    10\n    11\n  ]\n}",
+            PrettyPrinter::new().width(30).to_string(&m)
+        );
+        assert_eq!(
+            "{\n    \"empty\" []\n    \"numbers\" [0 1 2 3 4 5 6 7 8 9 10 11]\n}",
+            PrettyPrinter::new().width(50).indent(4).to_string(&m)
+        );
+        assert_eq!(to_string_pretty(&m), format!("{:#}", Edn(&m)));
+        assert_eq!(to_string(&m), Edn(&m).to_string());
+    }
+}
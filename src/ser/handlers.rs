@@ -0,0 +1,340 @@
+//! Runtime registry of write handlers, so types which cannot implement
+//! `TransitSerialize` (e.g. because of the orphan rule) still get an
+//! encoding, and built-in encodings may be replaced per writer.
+
+use super::*;
+use crate::value::TransitValue;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Encoding of values of type `T`, mirrors write handlers of other Transit
+/// implementations. Tags of one character are scalar (`~xrep`) when
+/// representation is a string, longer ones are written as `["~#tag", rep]`.
+pub trait WriteHandler<T> {
+    fn tag(&self, v: &T) -> String;
+    fn rep(&self, v: &T) -> TransitValue;
+    /// Representation used when the value is a map key, only values with
+    /// scalar tags may be keys
+    fn string_rep(&self, _v: &T) -> Option<String> {
+        None
+    }
+    /// Handler to be used by verbose writers instead of this one
+    fn verbose_handler(&self) -> Option<&dyn WriteHandler<T>> {
+        None
+    }
+}
+
+/// Handlers keyed by the type they encode
+#[derive(Default)]
+pub struct WriteHandlers {
+    handlers: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl fmt::Debug for WriteHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WriteHandlers({} handlers)", self.handlers.len())
+    }
+}
+
+impl WriteHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a handler for `T`, replacing the previous one
+    pub fn insert<T: 'static, H: WriteHandler<T> + 'static>(&mut self, handler: H) -> &mut Self {
+        let handler: Box<dyn WriteHandler<T>> = Box::new(handler);
+        self.handlers.insert(TypeId::of::<T>(), Box::new(handler));
+        self
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&dyn WriteHandler<T>> {
+        self.handlers
+            .get(&TypeId::of::<T>())
+            .and_then(|h| h.downcast_ref::<Box<dyn WriteHandler<T>>>())
+            .map(|h| h.as_ref())
+    }
+}
+
+fn pick<T>(h: &dyn WriteHandler<T>, verbose: bool) -> &dyn WriteHandler<T> {
+    match h.verbose_handler() {
+        Some(vh) if verbose => vh,
+        _ => h,
+    }
+}
+
+fn serialize_with<T, S: TransitSerializer>(
+    h: &dyn WriteHandler<T>,
+    v: &T,
+    serializer: &S,
+) -> S::Output {
+    let h = pick(h, serializer.is_verbose());
+    let tag = h.tag(v);
+    match h.rep(v) {
+        TransitValue::String(rep) if tag.chars().count() == 1 => {
+            serializer.serialize_string(&format!("~{}{}", tag, rep))
+        }
+        rep => serializer.serialize_tagged(&format!("~#{}", tag), &rep),
+    }
+}
+
+/// `None` means that the value is a composite key
+fn serialize_key_with<T, KS: TransitKeySerializer>(
+    h: &dyn WriteHandler<T>,
+    v: &T,
+    serializer: &KS,
+) -> Option<KS::Output> {
+    let h = pick(h, serializer.is_verbose());
+    let tag = h.tag(v);
+    if tag.chars().count() != 1 {
+        return None;
+    }
+    let rep = match h.string_rep(v) {
+        Some(rep) => rep,
+        None => match h.rep(v) {
+            TransitValue::String(rep) => rep,
+            _ => return None,
+        },
+    };
+    Some(serializer.serialize_key(&format!("~{}{}", tag, rep)))
+}
+
+/// Writes `v` with a handler registered in the serializer, if there is one
+pub(crate) fn serialize_handled<T: 'static, S: TransitSerializer>(
+    v: &T,
+    serializer: &S,
+) -> Option<S::Output> {
+    let h = serializer.write_handlers()?.get::<T>()?;
+    Some(serialize_with(h, v, serializer))
+}
+
+/// Writes `v` as a map key with a registered handler. Outer `None` means
+/// there is no handler, inner one that the value is a composite key.
+pub(crate) fn serialize_key_handled<T: 'static, KS: TransitKeySerializer>(
+    v: &T,
+    serializer: &KS,
+) -> Option<Option<KS::Output>> {
+    let h = serializer.write_handlers()?.get::<T>()?;
+    Some(serialize_key_with(h, v, serializer))
+}
+
+/// Value written with the given handler, for types which cannot implement
+/// `TransitSerialize`. A handler registered for `T` in the writer takes
+/// precedence over the given one.
+pub struct Handled<'a, T>(pub &'a T, pub &'a dyn WriteHandler<T>);
+
+impl<T: fmt::Debug> fmt::Debug for Handled<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Handled").field(self.0).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for Handled<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: Eq> Eq for Handled<'_, T> {}
+
+impl<T: PartialOrd> PartialOrd for Handled<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.0.partial_cmp(other.0)
+    }
+}
+
+impl<T: Ord> Ord for Handled<'_, T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(other.0)
+    }
+}
+
+impl<T: std::hash::Hash> std::hash::Hash for Handled<'_, T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<T: 'static> TransitSerialize for Handled<'_, T> {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        serialize_handled(self.0, serializer)
+            .unwrap_or_else(|| serialize_with(self.1, self.0, serializer))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        serialize_key_handled(self.0, serializer)
+            .unwrap_or_else(|| serialize_key_with(self.1, self.0, serializer))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ser::{json, json_verbose, msgpack, stream};
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    struct Point(i64, i64);
+
+    struct PointHandler;
+
+    impl WriteHandler<Point> for PointHandler {
+        fn tag(&self, _v: &Point) -> String {
+            "point".to_owned()
+        }
+
+        fn rep(&self, v: &Point) -> TransitValue {
+            TransitValue::Array(vec![TransitValue::Int(v.0), TransitValue::Int(v.1)])
+        }
+    }
+
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Color(&'static str);
+
+    struct ColorHandler;
+
+    impl WriteHandler<Color> for ColorHandler {
+        fn tag(&self, _v: &Color) -> String {
+            "x".to_owned()
+        }
+
+        fn rep(&self, v: &Color) -> TransitValue {
+            TransitValue::String(v.0.to_owned())
+        }
+    }
+
+    struct MillisHandler;
+
+    impl WriteHandler<DateTime<Utc>> for MillisHandler {
+        fn tag(&self, _v: &DateTime<Utc>) -> String {
+            "m".to_owned()
+        }
+
+        fn rep(&self, v: &DateTime<Utc>) -> TransitValue {
+            TransitValue::String(v.timestamp_millis().to_string())
+        }
+
+        fn verbose_handler(&self) -> Option<&dyn WriteHandler<DateTime<Utc>>> {
+            Some(&VerboseMillisHandler)
+        }
+    }
+
+    struct VerboseMillisHandler;
+
+    impl WriteHandler<DateTime<Utc>> for VerboseMillisHandler {
+        fn tag(&self, _v: &DateTime<Utc>) -> String {
+            "millis".to_owned()
+        }
+
+        fn rep(&self, v: &DateTime<Utc>) -> TransitValue {
+            TransitValue::Int(v.timestamp_millis())
+        }
+    }
+
+    fn handlers() -> WriteHandlers {
+        let mut h = WriteHandlers::new();
+        h.insert(PointHandler)
+            .insert(ColorHandler)
+            .insert(MillisHandler);
+        h
+    }
+
+    #[test]
+    fn foreign_types() {
+        let h = handlers();
+        let v = vec![Handled(&Point(1, 2), &PointHandler)];
+        assert_eq!(
+            json!([["~#point", [1, 2]]]),
+            json::to_transit_json_with(&v, &h)
+        );
+        assert_eq!(
+            json!([{"~#point": [1, 2]}]),
+            json_verbose::to_transit_json_with(&v, &h)
+        );
+        assert_eq!(
+            rmpv::Value::Array(vec![rmpv::Value::Array(vec![
+                "~#point".into(),
+                vec![rmpv::Value::from(1), rmpv::Value::from(2)].into(),
+            ])]),
+            msgpack::to_transit_msgpack_value_with(&v, &h)
+        );
+        let mut out = Vec::new();
+        stream::to_writer_with(&mut out, &v, stream::Mode::Json, &h).unwrap();
+        assert_eq!(r#"[["~#point",[1,2]]]"#, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn scalar_tag() {
+        let h = handlers();
+        assert_eq!(
+            json!(["~#'", "~xred"]),
+            json::to_transit_json_with(Handled(&Color("red"), &ColorHandler), &h)
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let h = handlers();
+        let mut m = BTreeMap::new();
+        m.insert(Handled(&Color("red"), &ColorHandler), 1);
+        assert_eq!(
+            json!(["^ ", "~xred", 1]),
+            json::to_transit_json_with(&m, &h)
+        );
+        assert_eq!(
+            json!({"~xred": 1}),
+            json_verbose::to_transit_json_with(&m, &h)
+        );
+    }
+
+    #[test]
+    fn override_builtin() {
+        let h = handlers();
+        let t = Utc.timestamp_millis_opt(1_500_000_000_000).unwrap();
+        assert_eq!(json!(["~#'", "~m1500000000000"]), json::to_transit_json(t));
+        assert_eq!(
+            json!(["~#'", "~m1500000000000"]),
+            json::to_transit_json_with(t, &h)
+        );
+        assert_eq!(
+            json!({"~#millis": 1_500_000_000_000i64}),
+            json_verbose::to_transit_json_with(t, &h)
+        );
+        assert_eq!(
+            json!({"~#'": "~t2017-07-14T02:40:00.000Z"}),
+            json_verbose::to_transit_json(t)
+        );
+    }
+
+    #[test]
+    fn own_handler() {
+        assert_eq!(
+            json!(["~#point", [0, 0]]),
+            json::to_transit_json(Handled(&Point(0, 0), &PointHandler))
+        );
+
+        struct FlatPointHandler;
+
+        impl WriteHandler<Point> for FlatPointHandler {
+            fn tag(&self, _v: &Point) -> String {
+                "p".to_owned()
+            }
+
+            fn rep(&self, v: &Point) -> TransitValue {
+                TransitValue::String(format!("{},{}", v.0, v.1))
+            }
+        }
+
+        let mut h = WriteHandlers::new();
+        h.insert(FlatPointHandler);
+        assert_eq!(
+            json!(["~#'", "~p0,0"]),
+            json::to_transit_json_with(Handled(&Point(0, 0), &PointHandler), &h)
+        );
+    }
+}
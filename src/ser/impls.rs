@@ -1,6 +1,18 @@
 use super::*;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::SystemTime;
+
+/// Strings starting with one of Transit's reserved characters are escaped
+/// with `~` so they will not be confused with tagged values or cache codes
+fn escape(s: &str) -> Cow<'_, str> {
+    if s.starts_with('~') || s.starts_with('^') || s.starts_with('`') {
+        Cow::Owned(format!("~{}", s))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
 
 impl<T: TransitSerialize + ?Sized> TransitSerialize for Box<T> {
     fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
@@ -15,7 +27,7 @@ impl<T: TransitSerialize + ?Sized> TransitSerialize for Box<T> {
     }
 }
 
-impl<'a, T: TransitSerialize + ?Sized> TransitSerialize for &'a T {
+impl<T: TransitSerialize + ?Sized> TransitSerialize for &T {
     fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
         (**self).transit_serialize(serializer)
     }
@@ -112,29 +124,71 @@ impl TransitSerialize for i32 {
     }
 }
 
+impl TransitSerialize for i64 {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        serializer.serialize_int(*self)
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        Some(serializer.serialize_key(&format!("~i{}", self)))
+    }
+}
+
+impl TransitSerialize for f64 {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        serializer.serialize_float(*self)
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        match special_float(*self) {
+            Some(s) => Some(serializer.serialize_key(s)),
+            None => Some(serializer.serialize_key(&format!("~d{}", self))),
+        }
+    }
+}
+
+impl TransitSerialize for f32 {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        f64::from(*self).transit_serialize(serializer)
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        f64::from(*self).transit_serialize_key(serializer)
+    }
+}
+
 impl TransitSerialize for String {
     fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
-        serializer.serialize_string(self)
+        serializer.serialize_string(&escape(self))
     }
 
     fn transit_serialize_key<KS: TransitKeySerializer>(
         &self,
         serializer: &KS,
     ) -> Option<KS::Output> {
-        Some(serializer.serialize_key(self))
+        Some(serializer.serialize_key(&escape(self)))
     }
 }
 
 impl TransitSerialize for &str {
     fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
-        serializer.serialize_string(self)
+        serializer.serialize_string(&escape(self))
     }
 
     fn transit_serialize_key<KS: TransitKeySerializer>(
         &self,
         serializer: &KS,
     ) -> Option<KS::Output> {
-        Some(serializer.serialize_key(self))
+        Some(serializer.serialize_key(&escape(self)))
     }
 }
 
@@ -155,15 +209,75 @@ impl<T: TransitSerialize> TransitSerialize for Option<T> {
     }
 }
 
+/// Spec encoding of instants: `~t` ISO-8601 with milliseconds in verbose
+/// mode, `~m` milliseconds since epoch otherwise
+fn instant(t: &DateTime<Utc>, verbose: bool) -> String {
+    if verbose {
+        format!("~t{}", t.format("%Y-%m-%dT%H:%M:%S%.3fZ"))
+    } else {
+        format!("~m{}", t.timestamp_millis())
+    }
+}
+
 impl TransitSerialize for DateTime<Utc> {
     fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
-        serializer.serialize_string(&format!("~t{:?}", self))
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_string(&instant(self, serializer.is_verbose())))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer).unwrap_or_else(|| {
+            Some(serializer.serialize_key(&instant(self, serializer.is_verbose())))
+        })
+    }
+}
+
+/// Written in UTC, offset is not a part of Transit instants
+impl TransitSerialize for DateTime<FixedOffset> {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| self.with_timezone(&Utc).transit_serialize(serializer))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| self.with_timezone(&Utc).transit_serialize_key(serializer))
+    }
+}
+
+/// Taken as UTC
+impl TransitSerialize for NaiveDateTime {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| self.and_utc().transit_serialize(serializer))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| self.and_utc().transit_serialize_key(serializer))
+    }
+}
+
+impl TransitSerialize for SystemTime {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| DateTime::<Utc>::from(*self).transit_serialize(serializer))
     }
 
     fn transit_serialize_key<KS: TransitKeySerializer>(
         &self,
         serializer: &KS,
     ) -> Option<KS::Output> {
-        Some(serializer.serialize_key(&format!("~t{:?}", self)))
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| DateTime::<Utc>::from(*self).transit_serialize_key(serializer))
     }
 }
@@ -1,206 +1,59 @@
+use super::handlers::WriteHandlers;
+use super::tree::{to_tree, Node, TreeValue};
 use super::*;
 use serde_json::Value as JsVal;
 
 pub fn to_transit_json<T: TransitSerialize>(v: T) -> JsVal {
-    v.transit_serialize(JsonSerializer::top())
+    to_tree(v, None)
 }
 
-pub struct JsonArraySerializer {
-    buf: Vec<JsVal>,
+/// Same as `to_transit_json`, consulting given handlers
+pub fn to_transit_json_with<T: TransitSerialize>(v: T, handlers: &WriteHandlers) -> JsVal {
+    to_tree(v, Some(handlers))
 }
 
-pub struct JsonMapSerializer {
-    buf_str_keys: Vec<Option<JsVal>>,
-    buf_keys: Vec<JsVal>,
-    buf_vals: Vec<JsVal>,
-    cmap: bool,
-}
-
-struct JsonTagArraySerializer {
-    tag: String,
-    array_serializer: JsonArraySerializer,
-}
-
-struct JsonTagMapSerializer {
-    tag: String,
-    map_serializer: JsonMapSerializer,
-}
-
-impl SerializeTagArray for JsonTagArraySerializer {
-    type Output = JsVal;
-
-    fn serialize_item<T: TransitSerialize>(&mut self, v: T) {
-        self.array_serializer.serialize_item(v);
-    }
-
-    fn end(self) -> Self::Output {
-        let val = self.array_serializer.end();
-        let mut vec = Vec::with_capacity(2);
-        vec.push(JsVal::String(self.tag));
-        vec.push(val);
-        JsVal::Array(vec)
-    }
-}
-
-impl SerializeTagMap for JsonTagMapSerializer {
-    type Output = JsVal;
-
-    fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: K, v: V) {
-        self.map_serializer.serialize_pair(k, v);
+impl TreeValue for JsVal {
+    fn null() -> Self {
+        JsVal::Null
     }
 
-    fn end(self) -> Self::Output {
-        let val = self.map_serializer.end();
-        let mut vec = Vec::with_capacity(2);
-        vec.push(JsVal::String(self.tag));
-        vec.push(val);
-        JsVal::Array(vec)
+    fn string(v: String) -> Self {
+        JsVal::String(v)
     }
-}
 
-impl SerializeMap for JsonMapSerializer {
-    type Output = JsVal;
-
-    fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: K, v: V) {
-        self.cmap = self.cmap || (K::TF_TYPE == TransitType::Composite);
-        self.buf_keys
-            .push(k.transit_serialize(JsonSerializer::default()));
-        self.buf_vals
-            .push(v.transit_serialize(JsonSerializer::default()));
-        // FIXME: compute cmap in the beginning and do not compute this vector if not needed
-        self.buf_str_keys
-            .push(k.transit_serialize_key(JsonSerializer::default()));
+    fn bool(v: bool) -> Self {
+        JsVal::Bool(v)
     }
 
-    fn end(self) -> Self::Output {
-        if self.cmap {
-            let mut val: Vec<JsVal> = Vec::with_capacity(2 * self.buf_keys.len());
-            for (k, v) in self.buf_keys.into_iter().zip(self.buf_vals.into_iter()) {
-                val.push(k);
-                val.push(v);
-            }
-            let mut vec = Vec::with_capacity(2);
-            vec.push(JsVal::String("~#cmap".to_owned()));
-            vec.push(JsVal::Array(val));
-            JsVal::Array(vec)
+    fn int(v: i64) -> Self {
+        if is_json_safe_int(v) {
+            v.into()
         } else {
-            let mut vec = Vec::with_capacity(self.buf_keys.len() + 1);
-            vec.push(JsVal::String("^".to_owned()));
-            for (k, v) in self.buf_str_keys.into_iter().zip(self.buf_vals.into_iter()) {
-                vec.push(k.expect("wut"));
-                vec.push(v);
-            }
-            JsVal::Array(vec)
+            JsVal::String(format!("~i{}", v))
         }
     }
-}
 
-impl SerializeArray for JsonArraySerializer {
-    type Output = JsVal;
-    fn serialize_item<T: TransitSerialize>(&mut self, v: T) {
-        self.buf
-            .push(v.transit_serialize(JsonSerializer::default()));
+    fn float(v: f64) -> Self {
+        v.into()
     }
 
-    fn end(self) -> Self::Output {
-        JsVal::Array(self.buf)
-    }
-}
-
-#[derive(Clone)]
-struct JsonSerializer {
-    top_level: bool,
-}
-
-impl Default for JsonSerializer {
-    fn default() -> Self {
-        JsonSerializer { top_level: false }
-    }
-}
-
-impl JsonSerializer {
-    fn top() -> Self {
-        JsonSerializer { top_level: true }
-    }
-
-    fn quote_check(self, v: JsVal) -> JsVal {
-        if self.top_level {
-            let mut vec = Vec::with_capacity(2);
-            vec.push(JsVal::String("~#".to_owned()));
-            vec.push(v);
-            JsVal::Array(vec)
-        } else {
-            v
-        }
-    }
-}
-
-impl TransitSerializer for JsonSerializer {
-    type Output = JsVal;
-    type SerializeArray = JsonArraySerializer;
-    type SerializeMap = JsonMapSerializer;
-    type SerializeTagArray = JsonTagArraySerializer;
-    type SerializeTagMap = JsonTagMapSerializer;
-
-    fn serialize_null(self) -> Self::Output {
-        self.quote_check(JsVal::Null)
-    }
-
-    fn serialize_string(self, v: &str) -> Self::Output {
-        self.quote_check(v.into())
-    }
-
-    fn serialize_bool(self, v: bool) -> Self::Output {
-        self.quote_check(v.into())
-    }
-
-    fn serialize_int(self, v: i64) -> Self::Output {
-        self.quote_check(v.into())
-    }
-
-    fn serialize_float(self, v: f64) -> Self::Output {
-        self.quote_check(v.into())
-    }
-
-    fn serialize_array(self, len: Option<usize>) -> Self::SerializeArray {
-        if let Some(len) = len {
-            JsonArraySerializer {
-                buf: Vec::with_capacity(len),
-            }
-        } else {
-            JsonArraySerializer { buf: Vec::new() }
-        }
+    fn bytes(v: &[u8]) -> Self {
+        JsVal::String(format!("~b{}", BASE64.encode(v)))
     }
 
-    fn serialize_map(self, len: Option<usize>) -> Self::SerializeMap {
-        if let Some(len) = len {
-            JsonMapSerializer {
-                buf_str_keys: Vec::with_capacity(len),
-                buf_keys: Vec::with_capacity(len),
-                buf_vals: Vec::with_capacity(len),
-                cmap: false,
-            }
-        } else {
-            JsonMapSerializer {
-                buf_str_keys: Vec::new(),
-                buf_keys: Vec::new(),
-                buf_vals: Vec::new(),
-                cmap: false,
-            }
-        }
+    fn array(v: Vec<Self>) -> Self {
+        JsVal::Array(v)
     }
 
-    fn serialize_tagged_array(self, tag: &str, len: Option<usize>) -> Self::SerializeTagArray {
-        JsonTagArraySerializer {
-            tag: tag.to_owned(),
-            array_serializer: self.serialize_array(len),
-        }
+    fn as_str(&self) -> Option<&str> {
+        self.as_str()
     }
 
-    fn serialize_tagged_map(self, tag: &str, len: Option<usize>) -> Self::SerializeTagMap {
-        JsonTagMapSerializer {
-            tag: tag.to_owned(),
-            map_serializer: self.serialize_map(len),
+    fn into_node(self) -> Node<Self> {
+        match self {
+            JsVal::String(s) => Node::String(s),
+            JsVal::Array(v) => Node::Array(v),
+            x => Node::Other(x),
         }
     }
 }
@@ -210,6 +63,7 @@ mod test {
     use super::*;
     use serde_json::json;
     use std::collections::{BTreeMap, BTreeSet};
+    use transit_derive::TransitSerialize;
 
     #[test]
     fn scalar_map_btree() {
@@ -218,7 +72,7 @@ mod test {
         m.insert(-6, "swag");
 
         let tr = to_transit_json(m);
-        assert_eq!(json!(["^", "~i-6", "swag", "~i4", "yolo"]), tr);
+        assert_eq!(json!(["^ ", "~i-6", "swag", "~i4", "yolo"]), tr);
     }
 
     #[test]
@@ -232,7 +86,7 @@ mod test {
 
         let tr = to_transit_json(m);
         assert_eq!(
-            json!(["~#cmap", [["^", "~?f", "tset", "~?t", "test"], 1337],]),
+            json!(["~#cmap", [["^ ", "~?f", "tset", "~?t", "test"], 1337]]),
             tr
         );
     }
@@ -240,7 +94,7 @@ mod test {
     #[test]
     fn quoting() {
         let tr = to_transit_json(5i32);
-        assert_eq!(json!(["~#", 5]), tr);
+        assert_eq!(json!(["~#'", 5]), tr);
     }
 
     #[test]
@@ -267,8 +121,8 @@ mod test {
         let tr = to_transit_json(v);
         assert_eq!(
             json!([
-                ["^", "hih", true, "test", true],
-                ["^", "not ok", false, "ok", true]
+                ["^ ", "hih", true, "test", true],
+                ["^ ", "not ok", false, "ok", true]
             ]),
             tr
         );
@@ -291,6 +145,128 @@ mod test {
         hm.insert(lol, 1337);
         let tr = to_transit_json(hm);
 
-        assert_eq!(json!(["^", "~_", 1337]), tr);
+        assert_eq!(json!(["^ ", "~_", 1337]), tr);
+    }
+
+    #[test]
+    fn caching() {
+        #[derive(Clone, TransitSerialize)]
+        struct Point {
+            x: i32,
+            y: i32,
+            label: String,
+        }
+
+        let points = vec![
+            Point {
+                x: 1,
+                y: 2,
+                label: "~#point".to_owned(),
+            },
+            Point {
+                x: 3,
+                y: 4,
+                label: "label".to_owned(),
+            },
+        ];
+        let tr = to_transit_json(points);
+        assert_eq!(
+            json!([
+                ["~#point", ["^ ", "x", 1, "y", 2, "label", "~~#point"]],
+                ["^0", ["^ ", "x", 3, "y", 4, "^1", "label"]]
+            ]),
+            tr
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        let v = vec!["~tilde", "^caret", "`tick", "plain"];
+        let tr = to_transit_json(v);
+        assert_eq!(json!(["~~tilde", "~^caret", "~`tick", "plain"]), tr);
+    }
+
+    #[test]
+    fn large_ints() {
+        let v = vec![1 << 53, (1 << 53) + 1, -(1 << 53) - 1, i64::MIN];
+        let tr = to_transit_json(&v);
+        assert_eq!(
+            json!([
+                9_007_199_254_740_992i64,
+                "~i9007199254740993",
+                "~i-9007199254740993",
+                "~i-9223372036854775808"
+            ]),
+            tr
+        );
+        let back: Vec<i64> = crate::de::json::from_transit_json(tr).unwrap();
+        assert_eq!(v, back);
+        assert_eq!(
+            json!(["~#'", "~i9223372036854775807"]),
+            to_transit_json(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn special_floats() {
+        use crate::value::TransitValue;
+
+        let v = vec![f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1.5];
+        let tr = to_transit_json(&v);
+        assert_eq!(json!(["~zNaN", "~zINF", "~z-INF", 1.5]), tr);
+        let back: Vec<f32> = crate::de::json::from_transit_json(tr).unwrap();
+        assert!(back[0].is_nan());
+        assert_eq!(
+            vec![f32::INFINITY, f32::NEG_INFINITY, 1.5],
+            back[1..].to_vec()
+        );
+
+        let mut m = BTreeMap::new();
+        m.insert("k", f64::INFINITY);
+        assert_eq!(json!(["^ ", "k", "~zINF"]), to_transit_json(&m));
+        let m = TransitValue::Map(vec![(
+            TransitValue::Float(f64::NEG_INFINITY),
+            TransitValue::Int(1),
+        )]);
+        let tr = to_transit_json(&m);
+        assert_eq!(json!(["^ ", "~z-INF", 1]), tr);
+        assert_eq!(
+            m,
+            crate::de::json::from_transit_json::<TransitValue>(tr).unwrap()
+        );
+    }
+
+    #[test]
+    fn instants() {
+        use crate::de;
+        use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let t = Utc.timestamp_millis_opt(1_554_121_800_123).unwrap();
+        let v = vec![t, t];
+        let tr = to_transit_json(&v);
+        assert_eq!(json!(["~m1554121800123", "~m1554121800123"]), tr);
+        let back: Vec<DateTime<Utc>> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(v, back);
+
+        let offset: DateTime<FixedOffset> =
+            DateTime::parse_from_rfc3339("2019-04-01T14:30:00.123+02:00").unwrap();
+        assert_eq!(json!(["~#'", "~m1554121800123"]), to_transit_json(offset));
+        let read: DateTime<FixedOffset> =
+            de::json::from_transit_json(json!(["~#'", "~t2019-04-01T14:30:00.123+02:00"])).unwrap();
+        assert_eq!(offset.offset(), read.offset());
+
+        let mut m = BTreeMap::new();
+        m.insert(
+            t.naive_utc(),
+            UNIX_EPOCH + Duration::from_millis(1_554_121_800_123),
+        );
+        let tr = to_transit_json(&m);
+        assert_eq!(json!(["^ ", "~m1554121800123", "~m1554121800123"]), tr);
+        let back: BTreeMap<NaiveDateTime, SystemTime> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+
+        let err: de::TResult<Vec<DateTime<Utc>>> = de::json::from_transit_json(json!(["~mnope"]));
+        assert!(err.is_err());
     }
 }
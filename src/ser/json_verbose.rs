@@ -1,29 +1,41 @@
+use super::handlers::WriteHandlers;
 use super::*;
 use itertools::Itertools;
 use serde_json::{map::Map as JsMap, Value as JsVal};
 
 pub fn to_transit_json<T: TransitSerialize>(v: T) -> JsVal {
-    v.transit_serialize(&JsonSerializer::top())
+    v.transit_serialize(&JsonSerializer::top(None))
 }
 
-struct JsonSerializer {
+/// Same as `to_transit_json`, consulting given handlers
+pub fn to_transit_json_with<T: TransitSerialize>(v: T, handlers: &WriteHandlers) -> JsVal {
+    v.transit_serialize(&JsonSerializer::top(Some(handlers)))
+}
+
+#[derive(Clone, Copy)]
+struct JsonSerializer<'h> {
     top_level: bool,
+    handlers: Option<&'h WriteHandlers>,
 }
 
-impl Default for JsonSerializer {
-    fn default() -> Self {
-        JsonSerializer { top_level: false }
+impl<'h> JsonSerializer<'h> {
+    fn top(handlers: Option<&'h WriteHandlers>) -> Self {
+        JsonSerializer {
+            top_level: true,
+            handlers,
+        }
     }
-}
 
-impl JsonSerializer {
-    fn top() -> Self {
-        JsonSerializer { top_level: true }
+    fn inner(&self) -> Self {
+        JsonSerializer {
+            top_level: false,
+            ..*self
+        }
     }
     fn quote_check(&self, v: JsVal) -> JsVal {
         if self.top_level {
             let mut m = JsMap::with_capacity(1);
-            m.insert("~#".to_owned(), v);
+            m.insert("~#'".to_owned(), v);
             JsVal::Object(m)
         } else {
             v
@@ -31,12 +43,20 @@ impl JsonSerializer {
     }
 }
 
-impl TransitSerializer for JsonSerializer {
+impl<'h> TransitSerializer for JsonSerializer<'h> {
     type Output = JsVal;
-    type ArraySerializer = JsonArraySerializer;
-    type MapSerializer = JsonMapSerializer;
-    type TaggedArraySerializer = JsonTaggedArraySerializer;
-    type TaggedMapSerializer = JsonTaggedMapSerializer;
+    type ArraySerializer = JsonArraySerializer<'h>;
+    type MapSerializer = JsonMapSerializer<'h>;
+    type TaggedArraySerializer = JsonTaggedArraySerializer<'h>;
+    type TaggedMapSerializer = JsonTaggedMapSerializer<'h>;
+
+    fn write_handlers(&self) -> Option<&WriteHandlers> {
+        self.handlers
+    }
+
+    fn is_verbose(&self) -> bool {
+        true
+    }
 
     fn serialize_null(&self) -> Self::Output {
         self.quote_check(JsVal::Null)
@@ -51,15 +71,22 @@ impl TransitSerializer for JsonSerializer {
     }
 
     fn serialize_int(&self, v: i64) -> Self::Output {
-        self.quote_check(v.into())
+        if is_json_safe_int(v) {
+            self.quote_check(v.into())
+        } else {
+            self.serialize_string(&format!("~i{}", v))
+        }
     }
 
     fn serialize_float(&self, v: f64) -> Self::Output {
-        self.quote_check(v.into())
+        match special_float(v) {
+            Some(s) => self.serialize_string(s),
+            None => self.quote_check(v.into()),
+        }
     }
 
     fn serialize_array(&self, len: Option<usize>) -> Self::ArraySerializer {
-        let inner = Self::default();
+        let inner = self.inner();
         if let Some(len) = len {
             JsonArraySerializer {
                 buf: Vec::with_capacity(len),
@@ -75,7 +102,7 @@ impl TransitSerializer for JsonSerializer {
     }
 
     fn serialize_map(&self, len: Option<usize>) -> Self::MapSerializer {
-        let inner = Self::default();
+        let inner = self.inner();
         if let Some(len) = len {
             JsonMapSerializer {
                 buf_keys: Vec::with_capacity(len),
@@ -107,12 +134,18 @@ impl TransitSerializer for JsonSerializer {
         }
     }
 
+    fn serialize_tagged<T: TransitSerialize>(&self, tag: &str, rep: &T) -> Self::Output {
+        let mut m = JsMap::with_capacity(1);
+        m.insert(tag.to_owned(), rep.transit_serialize(&self.inner()));
+        JsVal::Object(m)
+    }
+
     fn serialize_array_iter<'t, T, I>(&self, v: I) -> Self::Output
     where
         T: TransitSerialize + 't,
         I: Iterator<Item = &'t T>,
     {
-        let serializer = Self::default();
+        let serializer = self.inner();
         let v_ser = v.map(|x| x.transit_serialize(&serializer)).collect();
         JsVal::Array(v_ser)
     }
@@ -123,7 +156,7 @@ impl TransitSerializer for JsonSerializer {
         V: TransitSerialize + 't,
         I: Iterator<Item = (&'t K, &'t V)>,
     {
-        let serializer = Self::default();
+        let serializer = self.inner();
         let mut has_comp_key = false;
         let (ser_k, ser_v): (Vec<JsVal>, Vec<JsVal>) = v
             .map(|(key, value)| {
@@ -186,27 +219,35 @@ impl TransitSerializer for JsonSerializer {
     }
 }
 
-impl TransitKeySerializer for JsonSerializer {
+impl TransitKeySerializer for JsonSerializer<'_> {
     type Output = String;
 
+    fn write_handlers(&self) -> Option<&WriteHandlers> {
+        self.handlers
+    }
+
+    fn is_verbose(&self) -> bool {
+        true
+    }
+
     fn serialize_key(&self, v: &str) -> Self::Output {
         v.to_owned()
     }
 }
 
-pub struct JsonArraySerializer {
+pub struct JsonArraySerializer<'h> {
     buf: Vec<JsVal>,
-    inner_serializer: JsonSerializer,
+    inner_serializer: JsonSerializer<'h>,
 }
 
-pub struct JsonMapSerializer {
+pub struct JsonMapSerializer<'h> {
     buf_keys: Vec<JsVal>,
     buf_vals: Vec<JsVal>,
     cmap: bool,
-    inner_serializer: JsonSerializer,
+    inner_serializer: JsonSerializer<'h>,
 }
 
-impl TransitMapSerializer for JsonMapSerializer {
+impl TransitMapSerializer for JsonMapSerializer<'_> {
     type Output = JsVal;
 
     fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: &K, v: &V) {
@@ -246,7 +287,7 @@ impl TransitMapSerializer for JsonMapSerializer {
     }
 }
 
-impl TransitArraySerializer for JsonArraySerializer {
+impl TransitArraySerializer for JsonArraySerializer<'_> {
     type Output = JsVal;
     fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
         self.buf.push(v.transit_serialize(&self.inner_serializer));
@@ -257,17 +298,17 @@ impl TransitArraySerializer for JsonArraySerializer {
     }
 }
 
-struct JsonTaggedArraySerializer {
+struct JsonTaggedArraySerializer<'h> {
     tag: String,
-    array_serializer: JsonArraySerializer,
+    array_serializer: JsonArraySerializer<'h>,
 }
 
-struct JsonTaggedMapSerializer {
+struct JsonTaggedMapSerializer<'h> {
     tag: String,
-    map_serializer: JsonMapSerializer,
+    map_serializer: JsonMapSerializer<'h>,
 }
 
-impl TransitTaggedArraySerializer for JsonTaggedArraySerializer {
+impl TransitTaggedArraySerializer for JsonTaggedArraySerializer<'_> {
     type Output = JsVal;
 
     fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
@@ -282,7 +323,7 @@ impl TransitTaggedArraySerializer for JsonTaggedArraySerializer {
     }
 }
 
-impl TransitTaggedMapSerializer for JsonTaggedMapSerializer {
+impl TransitTaggedMapSerializer for JsonTaggedMapSerializer<'_> {
     type Output = JsVal;
 
     fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: &K, v: &V) {
@@ -344,12 +385,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn large_ints() {
+        let v = vec![1 << 53, (1 << 53) + 1, i64::MIN];
+        let tr = to_transit_json(&v);
+        assert_eq!(
+            json!([
+                9_007_199_254_740_992i64,
+                "~i9007199254740993",
+                "~i-9223372036854775808"
+            ]),
+            tr
+        );
+        let back: Vec<i64> = crate::de::json_verbose::from_transit_json(tr).unwrap();
+        assert_eq!(v, back);
+    }
+
     #[test]
     fn quoting() {
         let tr = to_transit_json(5i32);
         assert_eq!(
             json!({
-                "~#": 5
+                "~#'": 5
             }),
             tr
         );
@@ -436,7 +493,7 @@ mod test {
         let u = User {
             name: "Van".to_owned(),
             related: rel,
-            registered: Utc.ymd(1995, 10, 11).and_hms(0, 0, 0),
+            registered: Utc.with_ymd_and_hms(1995, 10, 11, 0, 0, 0).unwrap(),
             skills_by_rates: skills,
         };
         let tr = to_transit_json(u);
@@ -446,7 +503,7 @@ mod test {
                     "~#user": {
                         "name": "Van",
                         "related": {"~#set": ["Billy", "Mark", "Steve"]},
-                        "registered": "~t1995-10-11T00:00:00Z",
+                        "registered": "~t1995-10-11T00:00:00.000Z",
                         "skills_by_rates": {
                             "~i3": {"~#set": ["Git", "Linux"]},
                             "~i2": {"~#set": ["Performance artist"]},
@@ -541,4 +598,33 @@ mod test {
             tr
         );
     }
+
+    #[test]
+    fn instants() {
+        use chrono::{TimeZone, Utc};
+
+        let epoch = Utc.timestamp_millis_opt(0).unwrap();
+        assert_eq!(
+            json!({"~#'": "~t1970-01-01T00:00:00.000Z"}),
+            to_transit_json(epoch)
+        );
+        let mut m = BTreeMap::new();
+        m.insert(epoch, true);
+        assert_eq!(
+            json!({"~t1970-01-01T00:00:00.000Z": true}),
+            to_transit_json(&m)
+        );
+    }
+
+    #[test]
+    fn special_floats() {
+        use crate::value::TransitValue;
+
+        assert_eq!(json!(["~zNaN", 1.5]), to_transit_json(vec![f64::NAN, 1.5]));
+        let m = TransitValue::Map(vec![(
+            TransitValue::Float(f64::NEG_INFINITY),
+            TransitValue::Float(f64::INFINITY),
+        )]);
+        assert_eq!(json!({"~z-INF": "~zINF"}), to_transit_json(&m));
+    }
 }
@@ -0,0 +1,191 @@
+use super::handlers::WriteHandlers;
+use super::tree::{to_tree, Node, TreeValue};
+use super::*;
+use rmpv::Value as MpVal;
+
+/// Serializes into bytes of `application/transit+msgpack` content type
+pub fn to_transit_msgpack<T: TransitSerialize>(v: T) -> Vec<u8> {
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, &to_transit_msgpack_value(v))
+        .expect("Writing into Vec never fails");
+    buf
+}
+
+pub fn to_transit_msgpack_value<T: TransitSerialize>(v: T) -> MpVal {
+    to_tree(v, None)
+}
+
+/// Same as `to_transit_msgpack`, consulting given handlers
+pub fn to_transit_msgpack_with<T: TransitSerialize>(v: T, handlers: &WriteHandlers) -> Vec<u8> {
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, &to_transit_msgpack_value_with(v, handlers))
+        .expect("Writing into Vec never fails");
+    buf
+}
+
+pub fn to_transit_msgpack_value_with<T: TransitSerialize>(v: T, handlers: &WriteHandlers) -> MpVal {
+    to_tree(v, Some(handlers))
+}
+
+/// Ints are written natively, MessagePack has all 64 bits for them
+impl TreeValue for MpVal {
+    fn null() -> Self {
+        MpVal::Nil
+    }
+
+    fn string(v: String) -> Self {
+        MpVal::from(v)
+    }
+
+    fn bool(v: bool) -> Self {
+        MpVal::Boolean(v)
+    }
+
+    fn int(v: i64) -> Self {
+        MpVal::from(v)
+    }
+
+    fn float(v: f64) -> Self {
+        MpVal::F64(v)
+    }
+
+    fn bytes(v: &[u8]) -> Self {
+        MpVal::Binary(v.to_vec())
+    }
+
+    fn array(v: Vec<Self>) -> Self {
+        MpVal::Array(v)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        self.as_str()
+    }
+
+    fn into_node(self) -> Node<Self> {
+        match self {
+            MpVal::String(s) => {
+                Node::String(s.into_str().expect("Only valid UTF-8 strings are written"))
+            }
+            MpVal::Array(v) => Node::Array(v),
+            x => Node::Other(x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+    use transit_derive::TransitSerialize;
+
+    fn arr(v: Vec<MpVal>) -> MpVal {
+        MpVal::Array(v)
+    }
+
+    #[test]
+    fn bytes() {
+        let tr = to_transit_msgpack(vec![1, -1, i64::MAX]);
+        assert_eq!(
+            vec![0x93, 0x01, 0xff, 0xcf, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            tr
+        );
+    }
+
+    #[test]
+    fn scalars() {
+        let tr = to_transit_msgpack_value(vec![Some(1.5), None]);
+        assert_eq!(arr(vec![MpVal::F64(1.5), MpVal::Nil]), tr);
+    }
+
+    #[test]
+    fn quoting() {
+        let tr = to_transit_msgpack_value(5i32);
+        assert_eq!(arr(vec!["~#'".into(), 5.into()]), tr);
+    }
+
+    #[test]
+    fn map_composite_keys() {
+        let mut key1: BTreeMap<bool, &str> = BTreeMap::new();
+        key1.insert(true, "test");
+
+        let mut m = BTreeMap::new();
+        m.insert(key1, 1337);
+
+        let tr = to_transit_msgpack_value(m);
+        assert_eq!(
+            arr(vec![
+                "~#cmap".into(),
+                arr(vec![
+                    arr(vec!["^ ".into(), "~?t".into(), "test".into()]),
+                    1337.into()
+                ])
+            ]),
+            tr
+        );
+    }
+
+    #[test]
+    fn caching() {
+        #[derive(Clone, TransitSerialize)]
+        struct Reading {
+            sensor: String,
+            values: BTreeSet<i32>,
+        }
+
+        let readings = vec![
+            Reading {
+                sensor: "t1".to_owned(),
+                values: vec![1].into_iter().collect(),
+            },
+            Reading {
+                sensor: "t2".to_owned(),
+                values: BTreeSet::new(),
+            },
+        ];
+        let tr = to_transit_msgpack_value(readings);
+        assert_eq!(
+            arr(vec![
+                arr(vec![
+                    "~#reading".into(),
+                    arr(vec![
+                        "^ ".into(),
+                        "sensor".into(),
+                        "t1".into(),
+                        "values".into(),
+                        arr(vec!["~#set".into(), arr(vec![1.into()])])
+                    ])
+                ]),
+                arr(vec![
+                    "^0".into(),
+                    arr(vec![
+                        "^ ".into(),
+                        "^1".into(),
+                        "t2".into(),
+                        "^2".into(),
+                        arr(vec!["^3".into(), arr(vec![])])
+                    ])
+                ])
+            ]),
+            tr
+        );
+    }
+
+    #[test]
+    fn instants() {
+        use chrono::{TimeZone, Utc};
+
+        let epoch = Utc.timestamp_millis_opt(0).unwrap();
+        assert_eq!(
+            arr(vec!["~#'".into(), "~m0".into()]),
+            to_transit_msgpack_value(epoch)
+        );
+    }
+
+    #[test]
+    fn special_floats() {
+        assert_eq!(
+            arr(vec!["~z-INF".into(), MpVal::F64(1.5)]),
+            to_transit_msgpack_value(vec![f64::NEG_INFINITY, 1.5])
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use super::*;
+use crate::de::{Error, TResult};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde_json::{map::Map as JsMap, Value as JsVal};
+use std::cell::Cell;
+
+/// Exports a value as ordinary JSON for tools which do not speak Transit.
+/// The conversion is lossy: sets and lists become arrays, keywords,
+/// symbols and timestamps become strings, maps with composite keys become
+/// arrays of `[k, v]` pairs and tags are written as `{"tag": rep}`.
+/// Invalid Transit strings, e.g. `~?x`, fail with `Error::DoNotMatch`.
+pub fn to_plain_json<T: TransitSerialize>(v: T) -> TResult<JsVal> {
+    to_plain_json_with(v, &TagShape::default())
+}
+
+/// Same as `to_plain_json`, with tags written according to `TagShape`
+pub fn to_plain_json_with<T: TransitSerialize>(v: T, tags: &TagShape) -> TResult<JsVal> {
+    let invalid = Cell::new(None);
+    let js = v.transit_serialize(&PlainJsonSerializer {
+        tags,
+        invalid: &invalid,
+    });
+    match invalid.into_inner() {
+        Some(s) => Err(Error::DoNotMatch(format!(
+            "{:?} is not a valid Transit scalar",
+            s
+        ))),
+        None => Ok(js),
+    }
+}
+
+/// How tagged values other than sets and lists are written, tags are
+/// given without the `~#` prefix
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum TagShape {
+    /// Representation only, the tag is dropped
+    Untagged,
+    /// `{"tag": rep}`
+    #[default]
+    Object,
+    /// `["tag", rep]`
+    Pair,
+    /// `{<tag field>: "tag", <rep field>: rep}`
+    Fields { tag: String, rep: String },
+}
+
+impl TagShape {
+    fn apply(&self, tag: &str, rep: JsVal) -> JsVal {
+        let tag = tag.trim_start_matches("~#");
+        match self {
+            TagShape::Untagged => rep,
+            TagShape::Object => {
+                let mut m = JsMap::with_capacity(1);
+                m.insert(tag.to_owned(), rep);
+                JsVal::Object(m)
+            }
+            TagShape::Pair => JsVal::Array(vec![tag.into(), rep]),
+            TagShape::Fields { tag: t, rep: r } => {
+                let mut m = JsMap::with_capacity(2);
+                m.insert(t.clone(), tag.into());
+                m.insert(r.clone(), rep);
+                JsVal::Object(m)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PlainJsonSerializer<'c> {
+    tags: &'c TagShape,
+    /// First invalid Transit string, serializers cannot fail on their own
+    invalid: &'c Cell<Option<String>>,
+}
+
+impl PlainJsonSerializer<'_> {
+    /// Turns a Transit string, which may encode a scalar of any type, into
+    /// the closest JSON value
+    fn scalar(&self, s: &str) -> JsVal {
+        self.try_scalar(s).unwrap_or_else(|| {
+            let first = self.invalid.take().unwrap_or_else(|| s.to_owned());
+            self.invalid.set(Some(first));
+            JsVal::Null
+        })
+    }
+
+    fn try_scalar(&self, s: &str) -> Option<JsVal> {
+        let mut chars = s.chars();
+        let tag = match (chars.next(), chars.next()) {
+            (Some('~'), Some(tag)) => tag,
+            _ => return Some(s.into()),
+        };
+        let rep = chars.as_str();
+        let v = match tag {
+            '~' | '^' | '`' => s[1..].into(),
+            '_' => JsVal::Null,
+            '?' => match rep {
+                "t" => true.into(),
+                "f" => false.into(),
+                _ => return None,
+            },
+            'i' => rep.parse::<i64>().map_or_else(|_| rep.into(), JsVal::from),
+            'd' => rep.parse::<f64>().ok()?.into(),
+            // Not representable in JSON
+            'z' if matches!(rep, "NaN" | "INF" | "-INF") => JsVal::Null,
+            'z' => return None,
+            'm' => rep
+                .parse()
+                .ok()
+                .and_then(|ms| Utc.timestamp_millis_opt(ms).single())?
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .into(),
+            'c' if rep.is_empty() => return None,
+            'n' | 'f' | ':' | '$' | 'c' | 't' | 'u' | 'r' | 'b' => rep.into(),
+            tag => self.tags.apply(&tag.to_string(), rep.into()),
+        };
+        Some(v)
+    }
+}
+
+impl<'c> TransitSerializer for PlainJsonSerializer<'c> {
+    type Output = JsVal;
+    type ArraySerializer = PlainJsonArraySerializer<'c>;
+    type MapSerializer = PlainJsonMapSerializer<'c>;
+    type TaggedArraySerializer = PlainJsonArraySerializer<'c>;
+    type TaggedMapSerializer = PlainJsonMapSerializer<'c>;
+
+    fn serialize_null(&self) -> Self::Output {
+        JsVal::Null
+    }
+
+    fn serialize_string(&self, v: &str) -> Self::Output {
+        self.scalar(v)
+    }
+
+    fn serialize_bool(&self, v: bool) -> Self::Output {
+        v.into()
+    }
+
+    fn serialize_int(&self, v: i64) -> Self::Output {
+        v.into()
+    }
+
+    fn serialize_float(&self, v: f64) -> Self::Output {
+        v.into()
+    }
+
+    fn serialize_array(&self, len: Option<usize>) -> Self::ArraySerializer {
+        PlainJsonArraySerializer {
+            tag: None,
+            buf: Vec::with_capacity(len.unwrap_or(0)),
+            inner_serializer: *self,
+        }
+    }
+
+    fn serialize_map(&self, len: Option<usize>) -> Self::MapSerializer {
+        PlainJsonMapSerializer {
+            tag: None,
+            buf: Vec::with_capacity(len.unwrap_or(0)),
+            cmap: false,
+            inner_serializer: *self,
+        }
+    }
+
+    fn serialize_tagged_array(&self, tag: &str, len: Option<usize>) -> Self::TaggedArraySerializer {
+        PlainJsonArraySerializer {
+            tag: Some(tag.to_owned()),
+            ..self.serialize_array(len)
+        }
+    }
+
+    fn serialize_tagged_map(&self, tag: &str, len: Option<usize>) -> Self::TaggedMapSerializer {
+        PlainJsonMapSerializer {
+            tag: Some(tag.to_owned()),
+            ..self.serialize_map(len)
+        }
+    }
+
+    fn serialize_tagged<T: TransitSerialize>(&self, tag: &str, rep: &T) -> Self::Output {
+        let rep = rep.transit_serialize(self);
+        match tag {
+            "~#'" | "~#" => rep,
+            tag => self.tags.apply(tag, rep),
+        }
+    }
+
+    fn serialize_array_iter<'t, T, I>(&self, v: I) -> Self::Output
+    where
+        T: TransitSerialize + 't,
+        I: Iterator<Item = &'t T>,
+    {
+        JsVal::Array(v.map(|x| x.transit_serialize(self)).collect())
+    }
+
+    fn serialize_map_iter<'t, K, V, I>(&self, v: I) -> Self::Output
+    where
+        K: TransitSerialize + 't,
+        V: TransitSerialize + 't,
+        I: Iterator<Item = (&'t K, &'t V)>,
+    {
+        let mut ser = self.serialize_map(None);
+        v.for_each(|(k, v)| TransitMapSerializer::serialize_pair(&mut ser, k, v));
+        TransitMapSerializer::end(ser)
+    }
+
+    fn serialize_tagged_array_iter<'t, T, I>(&self, tag: &str, v: I) -> Self::Output
+    where
+        T: TransitSerialize + 't,
+        I: Iterator<Item = &'t T>,
+    {
+        let mut ser = self.serialize_tagged_array(tag, None);
+        v.for_each(|x| TransitTaggedArraySerializer::serialize_item(&mut ser, x));
+        TransitTaggedArraySerializer::end(ser)
+    }
+
+    fn serialize_tagged_map_iter<'t, K, V, I>(&self, tag: &str, v: I) -> Self::Output
+    where
+        K: TransitSerialize + 't,
+        V: TransitSerialize + 't,
+        I: Iterator<Item = (&'t K, &'t V)>,
+    {
+        let mut ser = self.serialize_tagged_map(tag, None);
+        v.for_each(|(k, v)| TransitTaggedMapSerializer::serialize_pair(&mut ser, k, v));
+        TransitTaggedMapSerializer::end(ser)
+    }
+}
+
+impl TransitKeySerializer for PlainJsonSerializer<'_> {
+    type Output = String;
+
+    fn serialize_key(&self, v: &str) -> Self::Output {
+        match self.scalar(v) {
+            JsVal::String(s) => s,
+            v => v.to_string(),
+        }
+    }
+}
+
+/// Sets and lists are written as plain arrays, other tags according to
+/// `TagShape`
+pub struct PlainJsonArraySerializer<'c> {
+    tag: Option<String>,
+    buf: Vec<JsVal>,
+    inner_serializer: PlainJsonSerializer<'c>,
+}
+
+impl TransitArraySerializer for PlainJsonArraySerializer<'_> {
+    type Output = JsVal;
+
+    fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
+        self.buf.push(v.transit_serialize(&self.inner_serializer));
+    }
+
+    fn end(self) -> Self::Output {
+        match self.tag.as_deref() {
+            None | Some("~#set") | Some("~#list") => JsVal::Array(self.buf),
+            Some(tag) => self
+                .inner_serializer
+                .tags
+                .apply(tag, JsVal::Array(self.buf)),
+        }
+    }
+}
+
+impl TransitTaggedArraySerializer for PlainJsonArraySerializer<'_> {
+    type Output = JsVal;
+
+    fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
+        TransitArraySerializer::serialize_item(self, v)
+    }
+
+    fn end(self) -> Self::Output {
+        TransitArraySerializer::end(self)
+    }
+}
+
+/// Maps with composite keys are written as arrays of `[k, v]` pairs
+pub struct PlainJsonMapSerializer<'c> {
+    tag: Option<String>,
+    buf: Vec<(Option<String>, JsVal, JsVal)>,
+    cmap: bool,
+    inner_serializer: PlainJsonSerializer<'c>,
+}
+
+impl TransitMapSerializer for PlainJsonMapSerializer<'_> {
+    type Output = JsVal;
+
+    fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: &K, v: &V) {
+        let str_key = k.transit_serialize_key(&self.inner_serializer);
+        self.cmap = self.cmap || str_key.is_none();
+        self.buf.push((
+            str_key,
+            k.transit_serialize(&self.inner_serializer),
+            v.transit_serialize(&self.inner_serializer),
+        ));
+    }
+
+    fn end(self) -> Self::Output {
+        let map = if self.cmap {
+            JsVal::Array(
+                self.buf
+                    .into_iter()
+                    .map(|(_, k, v)| JsVal::Array(vec![k, v]))
+                    .collect(),
+            )
+        } else {
+            let mut m = JsMap::with_capacity(self.buf.len());
+            for (key, _, value) in self.buf {
+                m.insert(key.expect("Scalar keys are always strings"), value);
+            }
+            JsVal::Object(m)
+        };
+        match self.tag {
+            Some(tag) => self.inner_serializer.tags.apply(&tag, map),
+            None => map,
+        }
+    }
+}
+
+impl TransitTaggedMapSerializer for PlainJsonMapSerializer<'_> {
+    type Output = JsVal;
+
+    fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: &K, v: &V) {
+        TransitMapSerializer::serialize_pair(self, k, v)
+    }
+
+    fn end(self) -> Self::Output {
+        TransitMapSerializer::end(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::value::TransitValue;
+    use serde_json::json;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn scalars() {
+        let v = vec![
+            TransitValue::Keyword("kw".to_owned()),
+            TransitValue::String("~tilde".to_owned()),
+            TransitValue::Instant(Utc.with_ymd_and_hms(2019, 4, 1, 12, 30, 0).unwrap()),
+            TransitValue::BigInt("123456789012345678901234567890".to_owned()),
+            TransitValue::Uuid(42),
+            TransitValue::Float(f64::NAN),
+            TransitValue::Null,
+        ];
+        assert_eq!(
+            json!([
+                "kw",
+                "~tilde",
+                "2019-04-01T12:30:00.000Z",
+                "123456789012345678901234567890",
+                "00000000-0000-0000-0000-00000000002a",
+                null,
+                null
+            ]),
+            to_plain_json(&v).unwrap()
+        );
+    }
+
+    #[test]
+    fn collections() {
+        let mut m = BTreeMap::new();
+        m.insert(4, vec!["a", "b"].into_iter().collect::<BTreeSet<_>>());
+        assert_eq!(json!({"4": ["a", "b"]}), to_plain_json(&m).unwrap());
+
+        let mut key = BTreeMap::new();
+        key.insert(true, 1);
+        let mut m = BTreeMap::new();
+        m.insert(key, "x");
+        assert_eq!(json!([[{"true": 1}, "x"]]), to_plain_json(&m).unwrap());
+    }
+
+    #[test]
+    fn tags() {
+        let point = TransitValue::Tagged(
+            "point".to_owned(),
+            Box::new(TransitValue::Array(vec![
+                TransitValue::Int(1),
+                TransitValue::Int(2),
+            ])),
+        );
+        assert_eq!(json!({"point": [1, 2]}), to_plain_json(&point).unwrap());
+        assert_eq!(
+            json!([1, 2]),
+            to_plain_json_with(&point, &TagShape::Untagged).unwrap()
+        );
+        assert_eq!(
+            json!(["point", [1, 2]]),
+            to_plain_json_with(&point, &TagShape::Pair).unwrap()
+        );
+        let fields = TagShape::Fields {
+            tag: "type".to_owned(),
+            rep: "value".to_owned(),
+        };
+        assert_eq!(
+            json!({"type": "point", "value": [1, 2]}),
+            to_plain_json_with(&point, &fields).unwrap()
+        );
+        let list = TransitValue::List(vec![TransitValue::Int(1)]);
+        assert_eq!(json!([1]), to_plain_json_with(&list, &fields).unwrap());
+    }
+
+    #[test]
+    fn invalid_scalars() {
+        use crate::value::RawValue;
+
+        for s in &["~?x", "~c", "~mnope", "~zINFINITY", "~d1.2.3"] {
+            let v = vec![RawValue::String(s.to_string())];
+            assert!(to_plain_json(&v).is_err(), "{} was accepted", s);
+        }
+        let v = RawValue::Map(vec![(RawValue::String("~?x".to_owned()), RawValue::Int(1))]);
+        assert!(to_plain_json(&v).is_err());
+    }
+}
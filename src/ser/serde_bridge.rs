@@ -0,0 +1,434 @@
+//! Adapter which lets `serde::Serialize` types be written as Transit by
+//! any backend, without deriving `TransitSerialize` for them.
+//!
+//! Serde data model is mapped the same way as `#[derive(TransitSerialize)]`
+//! does it: structs and enum variants become maps or arrays tagged with
+//! their lowercase name, unit variants become keywords, and maps with
+//! composite keys become `~#cmap`.
+
+use crate::de::{Error, TResult};
+use crate::value::TransitValue;
+use serde::ser::{self, Serialize};
+use std::convert::TryFrom;
+
+/// Converts a serde value into `TransitValue`, which may then be written
+/// by any Transit serializer
+pub fn to_transit_value<T: Serialize + ?Sized>(v: &T) -> TResult<TransitValue> {
+    v.serialize(ValueSerializer)
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+fn tagged(name: &str, rep: TransitValue) -> TransitValue {
+    TransitValue::Tagged(name.to_lowercase(), Box::new(rep))
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = TransitValue;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> TResult<TransitValue> {
+        Ok(TransitValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> TResult<TransitValue> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> TResult<TransitValue> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> TResult<TransitValue> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> TResult<TransitValue> {
+        Ok(TransitValue::Int(v))
+    }
+
+    /// Integers out of `i64` range are written as arbitrary precision ones
+    fn serialize_i128(self, v: i128) -> TResult<TransitValue> {
+        Ok(
+            i64::try_from(v)
+                .map_or_else(|_| TransitValue::BigInt(v.to_string()), TransitValue::Int),
+        )
+    }
+
+    fn serialize_u8(self, v: u8) -> TResult<TransitValue> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> TResult<TransitValue> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> TResult<TransitValue> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> TResult<TransitValue> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> TResult<TransitValue> {
+        Ok(
+            i64::try_from(v)
+                .map_or_else(|_| TransitValue::BigInt(v.to_string()), TransitValue::Int),
+        )
+    }
+
+    fn serialize_f32(self, v: f32) -> TResult<TransitValue> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> TResult<TransitValue> {
+        Ok(TransitValue::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> TResult<TransitValue> {
+        Ok(TransitValue::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> TResult<TransitValue> {
+        Ok(TransitValue::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> TResult<TransitValue> {
+        Ok(TransitValue::Bytes(v.to_owned()))
+    }
+
+    fn serialize_none(self) -> TResult<TransitValue> {
+        Ok(TransitValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, v: &T) -> TResult<TransitValue> {
+        v.serialize(self)
+    }
+
+    fn serialize_unit(self) -> TResult<TransitValue> {
+        Ok(TransitValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> TResult<TransitValue> {
+        Ok(TransitValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> TResult<TransitValue> {
+        Ok(TransitValue::Keyword(variant.to_lowercase()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        v: &T,
+    ) -> TResult<TransitValue> {
+        Ok(tagged(name, TransitValue::Array(vec![v.serialize(self)?])))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        v: &T,
+    ) -> TResult<TransitValue> {
+        Ok(tagged(
+            variant,
+            TransitValue::Array(vec![v.serialize(self)?]),
+        ))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> TResult<SeqSerializer> {
+        Ok(SeqSerializer::new(None, len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> TResult<SeqSerializer> {
+        Ok(SeqSerializer::new(None, Some(len)))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> TResult<SeqSerializer> {
+        Ok(SeqSerializer::new(Some(name), Some(len)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> TResult<SeqSerializer> {
+        Ok(SeqSerializer::new(Some(variant), Some(len)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> TResult<MapSerializer> {
+        Ok(MapSerializer::new(None, len))
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> TResult<MapSerializer> {
+        Ok(MapSerializer::new(Some(name), Some(len)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> TResult<MapSerializer> {
+        Ok(MapSerializer::new(Some(variant), Some(len)))
+    }
+}
+
+struct SeqSerializer {
+    tag: Option<&'static str>,
+    items: Vec<TransitValue>,
+}
+
+impl SeqSerializer {
+    fn new(tag: Option<&'static str>, len: Option<usize>) -> Self {
+        SeqSerializer {
+            tag,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, v: &T) -> TResult<()> {
+        self.items.push(v.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> TResult<TransitValue> {
+        let array = TransitValue::Array(self.items);
+        Ok(match self.tag {
+            Some(tag) => tagged(tag, array),
+            None => array,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = TransitValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, v: &T) -> TResult<()> {
+        self.push(v)
+    }
+
+    fn end(self) -> TResult<TransitValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = TransitValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, v: &T) -> TResult<()> {
+        self.push(v)
+    }
+
+    fn end(self) -> TResult<TransitValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = TransitValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, v: &T) -> TResult<()> {
+        self.push(v)
+    }
+
+    fn end(self) -> TResult<TransitValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = TransitValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, v: &T) -> TResult<()> {
+        self.push(v)
+    }
+
+    fn end(self) -> TResult<TransitValue> {
+        self.finish()
+    }
+}
+
+struct MapSerializer {
+    tag: Option<&'static str>,
+    pairs: Vec<(TransitValue, TransitValue)>,
+    key: Option<TransitValue>,
+}
+
+impl MapSerializer {
+    fn new(tag: Option<&'static str>, len: Option<usize>) -> Self {
+        MapSerializer {
+            tag,
+            pairs: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, k: &'static str, v: &T) -> TResult<()> {
+        let v = v.serialize(ValueSerializer)?;
+        self.pairs.push((TransitValue::String(k.to_owned()), v));
+        Ok(())
+    }
+
+    fn finish(self) -> TResult<TransitValue> {
+        let map = TransitValue::Map(self.pairs);
+        Ok(match self.tag {
+            Some(tag) => tagged(tag, map),
+            None => map,
+        })
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = TransitValue;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, k: &T) -> TResult<()> {
+        self.key = Some(k.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, v: &T) -> TResult<()> {
+        let k = self
+            .key
+            .take()
+            .ok_or_else(|| Error::Custom("map value without a key".to_owned()))?;
+        self.pairs.push((k, v.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> TResult<TransitValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = TransitValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, k: &'static str, v: &T) -> TResult<()> {
+        self.push(k, v)
+    }
+
+    fn end(self) -> TResult<TransitValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = TransitValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, k: &'static str, v: &T) -> TResult<()> {
+        self.push(k, v)
+    }
+
+    fn end(self) -> TResult<TransitValue> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ser::json_verbose::to_transit_json;
+    use serde::Serialize;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Serialize)]
+    struct Meters(f64);
+
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Line(Point, Point),
+        Rect { corner: Point, width: u64 },
+    }
+
+    #[test]
+    fn structs() {
+        let p = Point { x: 1, y: -2 };
+        assert_eq!(
+            json!({"~#point": {"x": 1, "y": -2}}),
+            crate::to_transit_json_serde(&p).unwrap()
+        );
+        assert_eq!(
+            json!({"~#meters": [1.5]}),
+            crate::to_transit_json_serde(&Meters(1.5)).unwrap()
+        );
+    }
+
+    #[test]
+    fn enums() {
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Circle(2.0),
+            Shape::Line(Point { x: 0, y: 0 }, Point { x: 1, y: 1 }),
+            Shape::Rect {
+                corner: Point { x: 0, y: 0 },
+                width: u64::MAX,
+            },
+        ];
+        assert_eq!(
+            json!([
+                "~:empty",
+                {"~#circle": [2.0]},
+                {"~#line": [{"~#point": {"x": 0, "y": 0}}, {"~#point": {"x": 1, "y": 1}}]},
+                {"~#rect": {"corner": {"~#point": {"x": 0, "y": 0}}, "width": "~n18446744073709551615"}}
+            ]),
+            crate::to_transit_json_serde(&shapes).unwrap()
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let mut m = BTreeMap::new();
+        m.insert(4, Some("~a"));
+        m.insert(5, None);
+        assert_eq!(
+            json!({"~i4": "~~a", "~i5": null}),
+            crate::to_transit_json_serde(&m).unwrap()
+        );
+
+        let mut m = BTreeMap::new();
+        m.insert((1, true), 'c');
+        let v = to_transit_value(&m).unwrap();
+        assert_eq!(json!({"~#cmap": [[1, true], "~cc"]}), to_transit_json(&v));
+    }
+}
@@ -0,0 +1,818 @@
+use super::handlers::WriteHandlers;
+use super::*;
+use crate::cache::{WriteCache, MAP_AS_ARRAY};
+use serde_json::ser::{CompactFormatter, Formatter, PrettyFormatter};
+use std::cell::RefCell;
+use std::io;
+
+/// JSON flavour produced by streaming serializer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Json,
+    JsonVerbose,
+}
+
+/// Writes Transit JSON token by token without building a document in memory
+pub fn to_writer<W: io::Write, T: TransitSerialize>(
+    writer: W,
+    v: &T,
+    mode: Mode,
+) -> io::Result<()> {
+    write_value(writer, CompactFormatter, mode, v, None)
+}
+
+/// Same as `to_writer`, consulting given handlers
+pub fn to_writer_with<W: io::Write, T: TransitSerialize>(
+    writer: W,
+    v: &T,
+    mode: Mode,
+    handlers: &WriteHandlers,
+) -> io::Result<()> {
+    write_value(writer, CompactFormatter, mode, v, Some(handlers))
+}
+
+pub fn to_writer_pretty<W: io::Write, T: TransitSerialize>(
+    writer: W,
+    v: &T,
+    mode: Mode,
+) -> io::Result<()> {
+    write_value(writer, PrettyFormatter::new(), mode, v, None)
+}
+
+pub fn to_vec<T: TransitSerialize>(v: &T, mode: Mode) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    to_writer(&mut buf, v, mode)?;
+    Ok(buf)
+}
+
+pub fn to_vec_pretty<T: TransitSerialize>(v: &T, mode: Mode) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    to_writer_pretty(&mut buf, v, mode)?;
+    Ok(buf)
+}
+
+pub fn to_string<T: TransitSerialize>(v: &T, mode: Mode) -> io::Result<String> {
+    to_vec(v, mode).map(|x| String::from_utf8(x).expect("JSON is always valid UTF-8"))
+}
+
+pub fn to_string_pretty<T: TransitSerialize>(v: &T, mode: Mode) -> io::Result<String> {
+    to_vec_pretty(v, mode).map(|x| String::from_utf8(x).expect("JSON is always valid UTF-8"))
+}
+
+/// Appends top-level values to a stream one per line, each of them with
+/// a fresh cache. Such streams are read by `de::stream::StreamDeserializer`.
+pub struct StreamWriter<W> {
+    writer: W,
+    mode: Mode,
+    handlers: Option<WriteHandlers>,
+}
+
+impl<W: io::Write> StreamWriter<W> {
+    pub fn new(writer: W, mode: Mode) -> Self {
+        StreamWriter {
+            writer,
+            mode,
+            handlers: None,
+        }
+    }
+
+    /// Writer consulting given handlers for every value
+    pub fn with_handlers(writer: W, mode: Mode, handlers: WriteHandlers) -> Self {
+        StreamWriter {
+            writer,
+            mode,
+            handlers: Some(handlers),
+        }
+    }
+
+    pub fn write<T: TransitSerialize>(&mut self, v: &T) -> io::Result<()> {
+        let handlers = self.handlers.as_ref();
+        write_value(&mut self.writer, CompactFormatter, self.mode, v, handlers)?;
+        self.writer.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn write_value<W: io::Write, F: Formatter, T: TransitSerialize>(
+    writer: W,
+    formatter: F,
+    mode: Mode,
+    v: &T,
+    handlers: Option<&WriteHandlers>,
+) -> io::Result<()> {
+    let state = RefCell::new(State {
+        writer,
+        formatter,
+        cache: WriteCache::default(),
+        mode,
+    });
+    v.transit_serialize(&StreamSerializer {
+        state: &state,
+        top_level: true,
+        handlers,
+    })
+}
+
+fn mixed_keys_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "composite key after scalar keys in a streamed map, use serialize_map_iter instead",
+    )
+}
+
+struct State<W, F> {
+    writer: W,
+    formatter: F,
+    cache: WriteCache,
+    mode: Mode,
+}
+
+/// Used to find out whether all keys of a map are scalar before writing it
+struct KeyProbe<'h> {
+    handlers: Option<&'h WriteHandlers>,
+    verbose: bool,
+}
+
+impl TransitKeySerializer for KeyProbe<'_> {
+    type Output = ();
+
+    fn serialize_key(&self, _v: &str) -> Self::Output {}
+
+    fn write_handlers(&self) -> Option<&WriteHandlers> {
+        self.handlers
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+/// JSON array or object being written, remembers if it has any items yet
+struct Compound<'s, W, F> {
+    state: &'s RefCell<State<W, F>>,
+    object: bool,
+    first: bool,
+}
+
+impl<'s, W: io::Write, F: Formatter> Compound<'s, W, F> {
+    fn open(state: &'s RefCell<State<W, F>>, object: bool) -> (Self, io::Result<()>) {
+        let c = Compound {
+            state,
+            object,
+            first: true,
+        };
+        let result = c.with(|f, w| {
+            if object {
+                f.begin_object(w)
+            } else {
+                f.begin_array(w)
+            }
+        });
+        (c, result)
+    }
+
+    fn with(&self, op: impl FnOnce(&mut F, &mut W) -> io::Result<()>) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        let State {
+            writer, formatter, ..
+        } = &mut *state;
+        op(formatter, writer)
+    }
+
+    /// Starts an array item or an object key
+    fn begin_item(&mut self) -> io::Result<()> {
+        let (first, object) = (self.first, self.object);
+        self.first = false;
+        self.with(|f, w| {
+            if object {
+                f.begin_object_key(w, first)
+            } else {
+                f.begin_array_value(w, first)
+            }
+        })
+    }
+
+    /// Ends an array item or starts an object value after its key
+    fn end_item(&mut self) -> io::Result<()> {
+        let object = self.object;
+        self.with(|f, w| {
+            if object {
+                f.end_object_key(w)?;
+                f.begin_object_value(w)
+            } else {
+                f.end_array_value(w)
+            }
+        })
+    }
+
+    /// Ends an object value
+    fn end_value(&mut self) -> io::Result<()> {
+        self.with(|f, w| f.end_object_value(w))
+    }
+
+    fn close(self) -> io::Result<()> {
+        let object = self.object;
+        self.with(|f, w| {
+            if object {
+                f.end_object(w)
+            } else {
+                f.end_array(w)
+            }
+        })
+    }
+}
+
+/// Tag wrapper `["~#tag", rep]` or `{"~#tag": rep}` with representation
+/// to be written in between of `open` and `close`
+struct Tagged<'s, W, F> {
+    compound: Compound<'s, W, F>,
+}
+
+impl<'s, W: io::Write, F: Formatter> Tagged<'s, W, F> {
+    fn open(ser: &StreamSerializer<'s, W, F>, tag: &str) -> (Self, io::Result<()>) {
+        let verbose = ser.verbose();
+        let (mut compound, result) = Compound::open(ser.state, verbose);
+        let result = result
+            .and_then(|_| compound.begin_item())
+            .and_then(|_| ser.write_str(tag, false))
+            .and_then(|_| compound.end_item())
+            .and_then(|_| {
+                if verbose {
+                    Ok(())
+                } else {
+                    compound.begin_item()
+                }
+            });
+        (Tagged { compound }, result)
+    }
+
+    fn close(mut self) -> io::Result<()> {
+        if self.compound.object {
+            self.compound.end_value()?;
+        } else {
+            self.compound.end_item()?;
+        }
+        self.compound.close()
+    }
+}
+
+struct StreamSerializer<'s, W, F> {
+    state: &'s RefCell<State<W, F>>,
+    top_level: bool,
+    handlers: Option<&'s WriteHandlers>,
+}
+
+impl<'s, W: io::Write, F: Formatter> StreamSerializer<'s, W, F> {
+    fn inner(&self) -> Self {
+        StreamSerializer {
+            state: self.state,
+            top_level: false,
+            handlers: self.handlers,
+        }
+    }
+
+    fn verbose(&self) -> bool {
+        self.state.borrow().mode == Mode::JsonVerbose
+    }
+
+    /// Writes a string, applying the cache in non-verbose mode
+    fn write_str(&self, v: &str, as_map_key: bool) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        let v = if state.mode == Mode::Json {
+            state.cache.cache_write(v.to_owned(), as_map_key)
+        } else {
+            v.to_owned()
+        };
+        serde_json::to_writer(&mut state.writer, &v).map_err(io::Error::from)
+    }
+
+    fn write_scalar(&self, op: impl FnOnce(&mut F, &mut W) -> io::Result<()>) -> io::Result<()> {
+        self.quote_check(|| {
+            let mut state = self.state.borrow_mut();
+            let State {
+                writer, formatter, ..
+            } = &mut *state;
+            op(formatter, writer)
+        })
+    }
+
+    fn quote_check(&self, write: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+        if self.top_level {
+            let (tagged, result) = Tagged::open(self, "~#'");
+            result?;
+            write()?;
+            tagged.close()
+        } else {
+            write()
+        }
+    }
+
+    fn map_serializer(&self, cmap: Option<bool>) -> StreamMapSerializer<'s, W, F> {
+        StreamMapSerializer {
+            inner_serializer: self.inner(),
+            cmap,
+            cmap_tag: None,
+            compound: None,
+            result: Ok(()),
+        }
+    }
+}
+
+impl<'s, W: io::Write, F: Formatter> TransitSerializer for StreamSerializer<'s, W, F> {
+    type Output = io::Result<()>;
+    type ArraySerializer = StreamArraySerializer<'s, W, F>;
+    type MapSerializer = StreamMapSerializer<'s, W, F>;
+    type TaggedArraySerializer = StreamTaggedArraySerializer<'s, W, F>;
+    type TaggedMapSerializer = StreamTaggedMapSerializer<'s, W, F>;
+
+    fn write_handlers(&self) -> Option<&WriteHandlers> {
+        self.handlers
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose()
+    }
+
+    fn serialize_null(&self) -> Self::Output {
+        self.write_scalar(|f, w| f.write_null(w))
+    }
+
+    fn serialize_string(&self, v: &str) -> Self::Output {
+        self.quote_check(|| self.write_str(v, false))
+    }
+
+    fn serialize_bool(&self, v: bool) -> Self::Output {
+        self.write_scalar(|f, w| f.write_bool(w, v))
+    }
+
+    fn serialize_int(&self, v: i64) -> Self::Output {
+        if is_json_safe_int(v) {
+            self.write_scalar(|f, w| f.write_i64(w, v))
+        } else {
+            self.serialize_string(&format!("~i{}", v))
+        }
+    }
+
+    /// Non-finite numbers are written as `~zNaN`, `~zINF` and `~z-INF` strings
+    fn serialize_float(&self, v: f64) -> Self::Output {
+        match special_float(v) {
+            Some(s) => self.serialize_string(s),
+            None => self.write_scalar(|f, w| f.write_f64(w, v)),
+        }
+    }
+
+    fn serialize_array(&self, _len: Option<usize>) -> Self::ArraySerializer {
+        let (compound, result) = Compound::open(self.state, false);
+        StreamArraySerializer {
+            compound,
+            inner_serializer: self.inner(),
+            result,
+        }
+    }
+
+    fn serialize_map(&self, _len: Option<usize>) -> Self::MapSerializer {
+        self.map_serializer(None)
+    }
+
+    fn serialize_tagged_array(&self, tag: &str, len: Option<usize>) -> Self::TaggedArraySerializer {
+        let (tagged, result) = Tagged::open(self, tag);
+        let mut array_serializer = self.serialize_array(len);
+        array_serializer.result = result.and(array_serializer.result);
+        StreamTaggedArraySerializer {
+            tagged,
+            array_serializer,
+        }
+    }
+
+    fn serialize_tagged_map(&self, tag: &str, len: Option<usize>) -> Self::TaggedMapSerializer {
+        let (tagged, result) = Tagged::open(self, tag);
+        let mut map_serializer = self.serialize_map(len);
+        map_serializer.result = result;
+        StreamTaggedMapSerializer {
+            tagged,
+            map_serializer,
+        }
+    }
+
+    fn serialize_tagged<T: TransitSerialize>(&self, tag: &str, rep: &T) -> Self::Output {
+        let (tagged, result) = Tagged::open(self, tag);
+        result?;
+        rep.transit_serialize(&self.inner())?;
+        tagged.close()
+    }
+
+    fn serialize_array_iter<'t, T, I>(&self, v: I) -> Self::Output
+    where
+        T: TransitSerialize + 't,
+        I: Iterator<Item = &'t T>,
+    {
+        let mut ser = self.serialize_array(None);
+        for x in v {
+            ser.serialize_item(x);
+        }
+        ser.end()
+    }
+
+    /// Unlike `serialize_map`, knows all the keys beforehand, so scalar and
+    /// composite keys may be mixed
+    fn serialize_map_iter<'t, K, V, I>(&self, v: I) -> Self::Output
+    where
+        K: TransitSerialize + 't,
+        V: TransitSerialize + 't,
+        I: Iterator<Item = (&'t K, &'t V)>,
+    {
+        let pairs: Vec<(&K, &V)> = v.collect();
+        let probe = KeyProbe {
+            handlers: self.handlers,
+            verbose: self.verbose(),
+        };
+        let cmap = pairs
+            .iter()
+            .any(|(k, _)| k.transit_serialize_key(&probe).is_none());
+        let mut ser = self.map_serializer(Some(cmap));
+        for (key, value) in pairs {
+            ser.serialize_pair(key, value);
+        }
+        ser.end()
+    }
+
+    fn serialize_tagged_array_iter<'t, T, I>(&self, tag: &str, v: I) -> Self::Output
+    where
+        T: TransitSerialize + 't,
+        I: Iterator<Item = &'t T>,
+    {
+        let (tagged, result) = Tagged::open(self, tag);
+        result?;
+        self.serialize_array_iter(v)?;
+        tagged.close()
+    }
+
+    fn serialize_tagged_map_iter<'t, K, V, I>(&self, tag: &str, v: I) -> Self::Output
+    where
+        K: TransitSerialize + 't,
+        V: TransitSerialize + 't,
+        I: Iterator<Item = (&'t K, &'t V)>,
+    {
+        let (tagged, result) = Tagged::open(self, tag);
+        result?;
+        self.serialize_map_iter(v)?;
+        tagged.close()
+    }
+}
+
+impl<W: io::Write, F: Formatter> TransitKeySerializer for StreamSerializer<'_, W, F> {
+    type Output = String;
+
+    fn serialize_key(&self, v: &str) -> Self::Output {
+        v.to_owned()
+    }
+
+    fn write_handlers(&self) -> Option<&WriteHandlers> {
+        self.handlers
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose()
+    }
+}
+
+/// Since items are written immediately, the first error is kept and
+/// returned by `end`
+pub struct StreamArraySerializer<'s, W, F> {
+    compound: Compound<'s, W, F>,
+    inner_serializer: StreamSerializer<'s, W, F>,
+    result: io::Result<()>,
+}
+
+impl<W: io::Write, F: Formatter> TransitArraySerializer for StreamArraySerializer<'_, W, F> {
+    type Output = io::Result<()>;
+
+    fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
+        if self.result.is_ok() {
+            self.result = self
+                .compound
+                .begin_item()
+                .and_then(|_| v.transit_serialize(&self.inner_serializer))
+                .and_then(|_| self.compound.end_item());
+        }
+    }
+
+    fn end(self) -> Self::Output {
+        self.result?;
+        self.compound.close()
+    }
+}
+
+/// Map is opened on the first pair, as it is the first key which decides
+/// whether it is written as `~#cmap` or not
+pub struct StreamMapSerializer<'s, W, F> {
+    inner_serializer: StreamSerializer<'s, W, F>,
+    cmap: Option<bool>,
+    cmap_tag: Option<Tagged<'s, W, F>>,
+    compound: Option<Compound<'s, W, F>>,
+    result: io::Result<()>,
+}
+
+impl<W: io::Write, F: Formatter> StreamMapSerializer<'_, W, F> {
+    fn open(&mut self, cmap: bool) -> io::Result<()> {
+        let ser = &self.inner_serializer;
+        let verbose = ser.verbose();
+        self.cmap = Some(cmap);
+        if cmap {
+            let (tagged, result) = Tagged::open(ser, "~#cmap");
+            let (compound, result2) = Compound::open(ser.state, false);
+            self.cmap_tag = Some(tagged);
+            self.compound = Some(compound);
+            result.and(result2)
+        } else {
+            let (mut compound, result) = Compound::open(ser.state, verbose);
+            let result = result.and_then(|_| {
+                if verbose {
+                    Ok(())
+                } else {
+                    compound.begin_item()?;
+                    ser.write_str(MAP_AS_ARRAY, false)?;
+                    compound.end_item()
+                }
+            });
+            self.compound = Some(compound);
+            result
+        }
+    }
+
+    fn write_pair<K: TransitSerialize, V: TransitSerialize>(
+        &mut self,
+        k: &K,
+        v: &V,
+    ) -> io::Result<()> {
+        let key = match self.cmap {
+            Some(true) => None,
+            _ => k.transit_serialize_key(&self.inner_serializer),
+        };
+        if self.compound.is_none() {
+            self.open(self.cmap.unwrap_or(key.is_none()))?;
+        }
+        let ser = &self.inner_serializer;
+        let compound = self.compound.as_mut().expect("Map is opened");
+        compound.begin_item()?;
+        if self.cmap == Some(true) {
+            k.transit_serialize(ser)?;
+            compound.end_item()?;
+            compound.begin_item()?;
+        } else {
+            let key = key.ok_or_else(mixed_keys_error)?;
+            ser.write_str(&key, true)?;
+            compound.end_item()?;
+            if !compound.object {
+                compound.begin_item()?;
+            }
+        }
+        v.transit_serialize(ser)?;
+        if compound.object {
+            compound.end_value()
+        } else {
+            compound.end_item()
+        }
+    }
+}
+
+impl<W: io::Write, F: Formatter> TransitMapSerializer for StreamMapSerializer<'_, W, F> {
+    type Output = io::Result<()>;
+
+    fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: &K, v: &V) {
+        if self.result.is_ok() {
+            self.result = self.write_pair(k, v);
+        }
+    }
+
+    fn end(mut self) -> Self::Output {
+        std::mem::replace(&mut self.result, Ok(()))?;
+        if self.compound.is_none() {
+            self.open(self.cmap.unwrap_or(false))?;
+        }
+        self.compound.expect("Map is opened").close()?;
+        if let Some(tagged) = self.cmap_tag {
+            tagged.close()?;
+        }
+        Ok(())
+    }
+}
+
+pub struct StreamTaggedArraySerializer<'s, W, F> {
+    tagged: Tagged<'s, W, F>,
+    array_serializer: StreamArraySerializer<'s, W, F>,
+}
+
+pub struct StreamTaggedMapSerializer<'s, W, F> {
+    tagged: Tagged<'s, W, F>,
+    map_serializer: StreamMapSerializer<'s, W, F>,
+}
+
+impl<W: io::Write, F: Formatter> TransitTaggedArraySerializer
+    for StreamTaggedArraySerializer<'_, W, F>
+{
+    type Output = io::Result<()>;
+
+    fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
+        self.array_serializer.serialize_item(v);
+    }
+
+    fn end(self) -> Self::Output {
+        self.array_serializer.end()?;
+        self.tagged.close()
+    }
+}
+
+impl<W: io::Write, F: Formatter> TransitTaggedMapSerializer
+    for StreamTaggedMapSerializer<'_, W, F>
+{
+    type Output = io::Result<()>;
+
+    fn serialize_pair<K: TransitSerialize, V: TransitSerialize>(&mut self, k: &K, v: &V) {
+        self.map_serializer.serialize_pair(k, v);
+    }
+
+    fn end(self) -> Self::Output {
+        self.map_serializer.end()?;
+        self.tagged.close()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value as JsVal;
+    use std::collections::{BTreeMap, BTreeSet};
+    use transit_derive::TransitSerialize;
+
+    #[derive(Clone, TransitSerialize)]
+    struct User {
+        name: String,
+        related: BTreeSet<String>,
+        skills_by_rates: BTreeMap<i32, Vec<String>>,
+        score: Option<f64>,
+    }
+
+    fn users() -> Vec<User> {
+        (0..3)
+            .map(|i| User {
+                name: format!("~user {}", i),
+                related: (0..i).map(|x| format!("friend {}", x)).collect(),
+                skills_by_rates: (0..i).map(|x| (x, vec!["Rust".to_owned()])).collect(),
+                score: if i == 0 { None } else { Some(i as f64 / 2.0) },
+            })
+            .collect()
+    }
+
+    fn composite() -> BTreeMap<BTreeMap<bool, String>, Vec<bool>> {
+        let mut key1: BTreeMap<bool, String> = BTreeMap::new();
+        key1.insert(true, "test".to_owned());
+        key1.insert(false, "tset".to_owned());
+
+        let mut m = BTreeMap::new();
+        m.insert(key1, vec![true]);
+        m.insert(BTreeMap::new(), vec![]);
+        m
+    }
+
+    fn parse(s: String) -> JsVal {
+        serde_json::from_str(&s).unwrap()
+    }
+
+    #[test]
+    fn same_as_json() {
+        let expected = crate::ser::json::to_transit_json(users());
+        assert_eq!(expected, parse(to_string(&users(), Mode::Json).unwrap()));
+        assert_eq!(
+            expected,
+            parse(to_string_pretty(&users(), Mode::Json).unwrap())
+        );
+
+        let expected = crate::ser::json::to_transit_json(composite());
+        assert_eq!(
+            expected,
+            parse(to_string(&composite(), Mode::Json).unwrap())
+        );
+    }
+
+    #[test]
+    fn same_as_json_verbose() {
+        let expected = crate::ser::json_verbose::to_transit_json(users());
+        assert_eq!(
+            expected,
+            parse(to_string(&users(), Mode::JsonVerbose).unwrap())
+        );
+
+        let expected = crate::ser::json_verbose::to_transit_json(composite());
+        assert_eq!(
+            expected,
+            parse(to_string(&composite(), Mode::JsonVerbose).unwrap())
+        );
+    }
+
+    #[test]
+    fn compact() {
+        let mut m = BTreeMap::new();
+        m.insert("key1", vec![1, 2]);
+        m.insert("key2", vec![]);
+        assert_eq!(
+            r#"["^ ","key1",[1,2],"key2",[]]"#,
+            to_string(&m, Mode::Json).unwrap()
+        );
+        assert_eq!(r#"["~#'",5]"#, to_string(&5, Mode::Json).unwrap());
+        assert_eq!(
+            r#"{"~#'":"~~x"}"#,
+            to_string(&"~x", Mode::JsonVerbose).unwrap()
+        );
+    }
+
+    #[test]
+    fn large_ints() {
+        let v = vec![1 << 53, (1 << 53) + 1, i64::MIN];
+        assert_eq!(
+            r#"[9007199254740992,"~i9007199254740993","~i-9223372036854775808"]"#,
+            to_string(&v, Mode::Json).unwrap()
+        );
+        assert_eq!(
+            crate::ser::json_verbose::to_transit_json(&v),
+            parse(to_string(&v, Mode::JsonVerbose).unwrap())
+        );
+        assert_eq!(
+            r#"["~#'","~i9007199254740993"]"#,
+            to_string(&((1i64 << 53) + 1), Mode::Json).unwrap()
+        );
+    }
+
+    #[test]
+    fn pretty() {
+        let mut hs = BTreeSet::new();
+        hs.insert(1);
+        assert_eq!(
+            "{\n  \"~#set\": [\n    1\n  ]\n}",
+            to_string_pretty(&hs, Mode::JsonVerbose).unwrap()
+        );
+    }
+
+    #[test]
+    fn writer() {
+        let mut w = StreamWriter::new(Vec::new(), Mode::Json);
+        w.write(&users()).unwrap();
+        w.write(&users()).unwrap();
+        let s = String::from_utf8(w.into_inner()).unwrap();
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(lines[0], lines[1]);
+    }
+
+    #[test]
+    fn mixed_keys() {
+        struct Mixed;
+
+        impl TransitSerialize for Mixed {
+            fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+                let mut ser = serializer.serialize_map(Some(2));
+                ser.serialize_pair(&1, &1);
+                ser.serialize_pair(&vec![1], &2);
+                ser.end()
+            }
+
+            fn transit_serialize_key<KS: TransitKeySerializer>(
+                &self,
+                _serializer: &KS,
+            ) -> Option<KS::Output> {
+                None
+            }
+        }
+
+        assert!(to_vec(&Mixed, Mode::Json).is_err());
+    }
+
+    #[test]
+    fn instants() {
+        use chrono::{TimeZone, Utc};
+
+        let epoch = Utc.timestamp_millis_opt(0).unwrap();
+        assert_eq!(r#"["~m0"]"#, to_string(&vec![epoch], Mode::Json).unwrap());
+        assert_eq!(
+            r#"{"~#'":"~t1970-01-01T00:00:00.000Z"}"#,
+            to_string(&epoch, Mode::JsonVerbose).unwrap()
+        );
+    }
+
+    #[test]
+    fn special_floats() {
+        let v = vec![f64::NAN, f64::INFINITY];
+        assert_eq!(r#"["~zNaN","~zINF"]"#, to_string(&v, Mode::Json).unwrap());
+        assert_eq!(
+            r#"["~zNaN","~zINF"]"#,
+            to_string(&v, Mode::JsonVerbose).unwrap()
+        );
+    }
+}
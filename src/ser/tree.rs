@@ -0,0 +1,321 @@
+//! Writer shared by non-verbose JSON and MessagePack. Both build a tree of
+//! values with maps as `["^ ", k, v, ...]` arrays and tags as
+//! `["~#tag", rep]`, then apply the write cache to the whole document.
+
+use super::handlers::WriteHandlers;
+use super::*;
+use crate::cache::{WriteCache, MAP_AS_ARRAY};
+use itertools::Itertools;
+
+/// Value of a document tree, e.g. `serde_json::Value` or `rmpv::Value`
+pub(crate) trait TreeValue: Sized {
+    fn null() -> Self;
+    fn string(v: String) -> Self;
+    fn bool(v: bool) -> Self;
+    fn int(v: i64) -> Self;
+    /// Only finite floats get here, the others are `~z` strings
+    fn float(v: f64) -> Self;
+    fn bytes(v: &[u8]) -> Self;
+    fn array(v: Vec<Self>) -> Self;
+    fn as_str(&self) -> Option<&str>;
+    fn into_node(self) -> Node<Self>;
+}
+
+/// Parts of a tree which the write cache looks into
+pub(crate) enum Node<V> {
+    String(String),
+    Array(Vec<V>),
+    Other(V),
+}
+
+pub(crate) fn to_tree<V: TreeValue, T: TransitSerialize>(
+    v: T,
+    handlers: Option<&WriteHandlers>,
+) -> V {
+    let tr: V = v.transit_serialize(&TreeSerializer::top(handlers));
+    write_cached(tr.into_node(), &mut WriteCache::default())
+}
+
+/// Applies the write cache to the whole document in order of appearance,
+/// which is the order a reader will fill its cache in
+fn write_cached<V: TreeValue>(v: Node<V>, cache: &mut WriteCache) -> V {
+    match v {
+        Node::String(s) => V::string(cache.cache_write(s, false)),
+        Node::Array(vec) => {
+            let is_map = vec.first().and_then(V::as_str) == Some(MAP_AS_ARRAY);
+            V::array(
+                vec.into_iter()
+                    .enumerate()
+                    .map(|(i, x)| match x.into_node() {
+                        Node::String(s) if is_map && i % 2 == 1 => {
+                            V::string(cache.cache_write(s, true))
+                        }
+                        x => write_cached(x, cache),
+                    })
+                    .collect(),
+            )
+        }
+        Node::Other(x) => x,
+    }
+}
+
+fn tagged<V: TreeValue>(tag: String, v: V) -> V {
+    V::array(vec![V::string(tag), v])
+}
+
+pub(crate) struct TreeSerializer<'h, V> {
+    top_level: bool,
+    handlers: Option<&'h WriteHandlers>,
+    value: std::marker::PhantomData<fn() -> V>,
+}
+
+impl<V> Clone for TreeSerializer<'_, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for TreeSerializer<'_, V> {}
+
+impl<'h, V: TreeValue> TreeSerializer<'h, V> {
+    fn top(handlers: Option<&'h WriteHandlers>) -> Self {
+        TreeSerializer {
+            top_level: true,
+            handlers,
+            value: std::marker::PhantomData,
+        }
+    }
+
+    fn inner(&self) -> Self {
+        TreeSerializer {
+            top_level: false,
+            ..*self
+        }
+    }
+
+    fn quote_check(&self, v: V) -> V {
+        if self.top_level {
+            tagged("~#'".to_owned(), v)
+        } else {
+            v
+        }
+    }
+}
+
+impl<'h, V: TreeValue> TransitSerializer for TreeSerializer<'h, V> {
+    type Output = V;
+    type ArraySerializer = TreeArraySerializer<'h, V>;
+    type MapSerializer = TreeMapSerializer<'h, V>;
+    type TaggedArraySerializer = TreeTaggedArraySerializer<'h, V>;
+    type TaggedMapSerializer = TreeTaggedMapSerializer<'h, V>;
+
+    fn write_handlers(&self) -> Option<&WriteHandlers> {
+        self.handlers
+    }
+
+    fn serialize_null(&self) -> Self::Output {
+        self.quote_check(V::null())
+    }
+
+    fn serialize_string(&self, v: &str) -> Self::Output {
+        self.quote_check(V::string(v.to_owned()))
+    }
+
+    fn serialize_bool(&self, v: bool) -> Self::Output {
+        self.quote_check(V::bool(v))
+    }
+
+    fn serialize_int(&self, v: i64) -> Self::Output {
+        self.quote_check(V::int(v))
+    }
+
+    fn serialize_float(&self, v: f64) -> Self::Output {
+        match special_float(v) {
+            Some(s) => self.serialize_string(s),
+            None => self.quote_check(V::float(v)),
+        }
+    }
+
+    fn serialize_bytes(&self, v: &[u8]) -> Self::Output {
+        self.quote_check(V::bytes(v))
+    }
+
+    fn serialize_array(&self, len: Option<usize>) -> Self::ArraySerializer {
+        TreeArraySerializer {
+            buf: Vec::with_capacity(len.unwrap_or(0)),
+            inner_serializer: self.inner(),
+        }
+    }
+
+    fn serialize_map(&self, len: Option<usize>) -> Self::MapSerializer {
+        let len = len.unwrap_or(0);
+        TreeMapSerializer {
+            buf_keys: Vec::with_capacity(len),
+            buf_vals: Vec::with_capacity(len),
+            cmap: false,
+            inner_serializer: self.inner(),
+        }
+    }
+
+    fn serialize_tagged_array(&self, tag: &str, len: Option<usize>) -> Self::TaggedArraySerializer {
+        TreeTaggedArraySerializer {
+            tag: tag.to_owned(),
+            array_serializer: self.serialize_array(len),
+        }
+    }
+
+    fn serialize_tagged_map(&self, tag: &str, len: Option<usize>) -> Self::TaggedMapSerializer {
+        TreeTaggedMapSerializer {
+            tag: tag.to_owned(),
+            map_serializer: self.serialize_map(len),
+        }
+    }
+
+    fn serialize_tagged<T: TransitSerialize>(&self, tag: &str, rep: &T) -> Self::Output {
+        tagged(tag.to_owned(), rep.transit_serialize(&self.inner()))
+    }
+
+    fn serialize_array_iter<'t, T, I>(&self, v: I) -> Self::Output
+    where
+        T: TransitSerialize + 't,
+        I: Iterator<Item = &'t T>,
+    {
+        let serializer = self.inner();
+        let v_ser = v.map(|x| x.transit_serialize(&serializer)).collect();
+        V::array(v_ser)
+    }
+
+    fn serialize_map_iter<'t, K, W, I>(&self, v: I) -> Self::Output
+    where
+        K: TransitSerialize + 't,
+        W: TransitSerialize + 't,
+        I: Iterator<Item = (&'t K, &'t W)>,
+    {
+        let mut ser = self.serialize_map(None);
+        for (key, value) in v {
+            ser.serialize_pair(key, value);
+        }
+        ser.end()
+    }
+
+    fn serialize_tagged_array_iter<'t, T, I>(&self, tag: &str, v: I) -> Self::Output
+    where
+        T: TransitSerialize + 't,
+        I: Iterator<Item = &'t T>,
+    {
+        tagged(tag.to_owned(), self.serialize_array_iter(v))
+    }
+
+    fn serialize_tagged_map_iter<'t, K, W, I>(&self, tag: &str, v: I) -> Self::Output
+    where
+        K: TransitSerialize + 't,
+        W: TransitSerialize + 't,
+        I: Iterator<Item = (&'t K, &'t W)>,
+    {
+        tagged(tag.to_owned(), self.serialize_map_iter(v))
+    }
+}
+
+impl<V: TreeValue> TransitKeySerializer for TreeSerializer<'_, V> {
+    type Output = String;
+
+    fn write_handlers(&self) -> Option<&WriteHandlers> {
+        self.handlers
+    }
+
+    fn serialize_key(&self, v: &str) -> Self::Output {
+        v.to_owned()
+    }
+}
+
+pub struct TreeArraySerializer<'h, V> {
+    buf: Vec<V>,
+    inner_serializer: TreeSerializer<'h, V>,
+}
+
+/// Keeps both key representations until the end, as a composite key may
+/// turn the whole map into `~#cmap`
+pub struct TreeMapSerializer<'h, V> {
+    buf_keys: Vec<(Option<String>, V)>,
+    buf_vals: Vec<V>,
+    cmap: bool,
+    inner_serializer: TreeSerializer<'h, V>,
+}
+
+impl<V: TreeValue> TransitMapSerializer for TreeMapSerializer<'_, V> {
+    type Output = V;
+
+    fn serialize_pair<K: TransitSerialize, W: TransitSerialize>(&mut self, k: &K, v: &W) {
+        let str_key = k.transit_serialize_key(&self.inner_serializer);
+        self.cmap = self.cmap || str_key.is_none();
+        self.buf_keys
+            .push((str_key, k.transit_serialize(&self.inner_serializer)));
+        self.buf_vals
+            .push(v.transit_serialize(&self.inner_serializer));
+    }
+
+    fn end(self) -> Self::Output {
+        if self.cmap {
+            let interleaved: Vec<V> = self
+                .buf_keys
+                .into_iter()
+                .map(|(_, k)| k)
+                .interleave(self.buf_vals)
+                .collect();
+            tagged("~#cmap".to_owned(), V::array(interleaved))
+        } else {
+            let mut vec = Vec::with_capacity(2 * self.buf_keys.len() + 1);
+            vec.push(V::string(MAP_AS_ARRAY.to_owned()));
+            for ((key, _), value) in self.buf_keys.into_iter().zip(self.buf_vals) {
+                vec.push(V::string(key.expect("Scalar keys are always strings")));
+                vec.push(value);
+            }
+            V::array(vec)
+        }
+    }
+}
+
+impl<V: TreeValue> TransitArraySerializer for TreeArraySerializer<'_, V> {
+    type Output = V;
+    fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
+        self.buf.push(v.transit_serialize(&self.inner_serializer));
+    }
+
+    fn end(self) -> Self::Output {
+        V::array(self.buf)
+    }
+}
+
+pub struct TreeTaggedArraySerializer<'h, V> {
+    tag: String,
+    array_serializer: TreeArraySerializer<'h, V>,
+}
+
+pub struct TreeTaggedMapSerializer<'h, V> {
+    tag: String,
+    map_serializer: TreeMapSerializer<'h, V>,
+}
+
+impl<V: TreeValue> TransitTaggedArraySerializer for TreeTaggedArraySerializer<'_, V> {
+    type Output = V;
+
+    fn serialize_item<T: TransitSerialize>(&mut self, v: &T) {
+        self.array_serializer.serialize_item(v);
+    }
+
+    fn end(self) -> Self::Output {
+        tagged(self.tag, self.array_serializer.end())
+    }
+}
+
+impl<V: TreeValue> TransitTaggedMapSerializer for TreeTaggedMapSerializer<'_, V> {
+    type Output = V;
+
+    fn serialize_pair<K: TransitSerialize, W: TransitSerialize>(&mut self, k: &K, v: &W) {
+        self.map_serializer.serialize_pair(k, v);
+    }
+
+    fn end(self) -> Self::Output {
+        tagged(self.tag, self.map_serializer.end())
+    }
+}
@@ -0,0 +1,463 @@
+//! Transit types which have no counterpart in the standard library
+
+use crate::de::{Error, TResult, TransitDeserialize, TransitDeserializer, TransitType};
+use crate::ser::{handlers, TransitKeySerializer, TransitSerialize, TransitSerializer};
+use crate::value::TransitValue;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::fmt;
+
+/// Splits `ns/name` at the first slash, `/` alone is a name
+fn split_namespace(s: &str) -> (Option<&str>, &str) {
+    match s.find('/') {
+        Some(i) if i > 0 && i < s.len() - 1 => (Some(&s[..i]), &s[i + 1..]),
+        _ => (None, s),
+    }
+}
+
+macro_rules! named_type {
+    ($(#[$doc:meta])* $t:ident, $prefix:expr, $display:expr) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $t(String);
+
+        impl $t {
+            /// Takes the full name, e.g. `user/id`
+            pub fn new<S: Into<String>>(s: S) -> Self {
+                $t(s.into())
+            }
+
+            pub fn namespaced(namespace: &str, name: &str) -> Self {
+                $t(format!("{}/{}", namespace, name))
+            }
+
+            pub fn namespace(&self) -> Option<&str> {
+                split_namespace(&self.0).0
+            }
+
+            pub fn name(&self) -> &str {
+                split_namespace(&self.0).1
+            }
+
+            /// Name with namespace, without the leading sigil
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl From<&str> for $t {
+            fn from(s: &str) -> Self {
+                $t::new(s)
+            }
+        }
+
+        impl From<$t> for TransitValue {
+            fn from(v: $t) -> Self {
+                TransitValue::$t(v.0)
+            }
+        }
+
+        impl fmt::Display for $t {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}{}", $display, self.0)
+            }
+        }
+
+        impl TransitSerialize for $t {
+            fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+                handlers::serialize_handled(self, serializer).unwrap_or_else(|| {
+                    serializer.serialize_string(&format!("{}{}", $prefix, self.0))
+                })
+            }
+
+            fn transit_serialize_key<KS: TransitKeySerializer>(
+                &self,
+                serializer: &KS,
+            ) -> Option<KS::Output> {
+                handlers::serialize_key_handled(self, serializer).unwrap_or_else(|| {
+                    Some(serializer.serialize_key(&format!("{}{}", $prefix, self.0)))
+                })
+            }
+        }
+
+        impl<'de> TransitDeserialize<'de> for $t {
+            const TF_TYPE: TransitType = TransitType::Scalar;
+
+            fn transit_deserialize<D: TransitDeserializer<'de>>(
+                deserializer: D,
+                input: D::Input,
+            ) -> TResult<Self> {
+                let s = deserializer.deserialize_string(input)?;
+                match s.strip_prefix($prefix) {
+                    Some(name) => Ok($t(name.to_owned())),
+                    None => Err(Error::DoNotMatch(format!(
+                        "{:?} is not {}",
+                        s,
+                        stringify!($t)
+                    ))),
+                }
+            }
+
+            fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+                deserializer: D,
+                input: D::Input,
+            ) -> TResult<Self> {
+                Self::transit_deserialize(deserializer, input)
+            }
+        }
+    };
+}
+
+named_type!(
+    /// Keyword, `~:ns/name`, displayed as `:ns/name` like in Clojure
+    Keyword,
+    "~:",
+    ":"
+);
+
+named_type!(
+    /// Symbol, `~$ns/name`
+    Symbol,
+    "~$",
+    ""
+);
+
+/// URI, `~r`. Only characters are validated, see the `url` feature for
+/// a complete parser.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uri(String);
+
+impl Uri {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let invalid = |c: char| {
+            c.is_whitespace()
+                || c.is_control()
+                || matches!(c, '<' | '>' | '"' | '{' | '}' | '|' | '\\' | '^' | '`')
+        };
+        if s.is_empty() {
+            Err(Error::CannotParse("URI cannot be empty".to_owned()))
+        } else if let Some(c) = s.chars().find(|c| invalid(*c)) {
+            Err(Error::CannotParse(format!(
+                "{:?} is not allowed in URI {:?}",
+                c, s
+            )))
+        } else {
+            Ok(Uri(s.to_owned()))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::str::FromStr for Uri {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Uri::parse(s)
+    }
+}
+
+impl From<Uri> for TransitValue {
+    fn from(v: Uri) -> Self {
+        TransitValue::Uri(v.0)
+    }
+}
+
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TransitSerialize for Uri {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_string(&format!("~r{}", self.0)))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer)
+            .unwrap_or_else(|| Some(serializer.serialize_key(&format!("~r{}", self.0))))
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for Uri {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let s = deserializer.deserialize_string(input)?;
+        match s.strip_prefix("~r") {
+            Some(uri) => Uri::parse(uri),
+            None => Err(Error::DoNotMatch(format!("{:?} is not Uri", s))),
+        }
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::transit_deserialize(deserializer, input)
+    }
+}
+
+/// Borrowed binary data, `~b` base64 in JSON and native binary in
+/// MessagePack. `Vec<u8>` is an array of ints instead. Write handlers are
+/// registered for `ByteBuf`, they are used for `Bytes` as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bytes<'a>(pub &'a [u8]);
+
+fn has_handler(handlers: Option<&handlers::WriteHandlers>) -> bool {
+    handlers.and_then(|h| h.get::<ByteBuf>()).is_some()
+}
+
+impl TransitSerialize for Bytes<'_> {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        if has_handler(serializer.write_handlers()) {
+            ByteBuf(self.0.to_vec()).transit_serialize(serializer)
+        } else {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        if has_handler(serializer.write_handlers()) {
+            ByteBuf(self.0.to_vec()).transit_serialize_key(serializer)
+        } else {
+            Some(serializer.serialize_key(&format!("~b{}", BASE64.encode(self.0))))
+        }
+    }
+}
+
+/// Owned binary data, see `Bytes`
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteBuf(pub Vec<u8>);
+
+impl ByteBuf {
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl From<Vec<u8>> for ByteBuf {
+    fn from(v: Vec<u8>) -> Self {
+        ByteBuf(v)
+    }
+}
+
+impl std::ops::Deref for ByteBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<ByteBuf> for TransitValue {
+    fn from(v: ByteBuf) -> Self {
+        TransitValue::Bytes(v.0)
+    }
+}
+
+impl TransitSerialize for ByteBuf {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        handlers::serialize_handled(self, serializer)
+            .unwrap_or_else(|| serializer.serialize_bytes(&self.0))
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        handlers::serialize_key_handled(self, serializer).unwrap_or_else(|| {
+            Some(serializer.serialize_key(&format!("~b{}", BASE64.encode(&self.0))))
+        })
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for ByteBuf {
+    const TF_TYPE: TransitType = TransitType::Scalar;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        deserializer.deserialize_bytes(input).map(ByteBuf)
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        crate::de::bytes_from_str(&deserializer.deserialize_string(input)?).map(ByteBuf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::de;
+    use crate::ser;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn names() {
+        let k = Keyword::new("user/id");
+        assert_eq!(Some("user"), k.namespace());
+        assert_eq!("id", k.name());
+        assert_eq!(k, Keyword::namespaced("user", "id"));
+        assert_eq!(":user/id", k.to_string());
+
+        let s = Symbol::from("/");
+        assert_eq!(None, s.namespace());
+        assert_eq!("/", s.name());
+        assert_eq!(
+            Some("clojure.core"),
+            Symbol::new("clojure.core//").namespace()
+        );
+        assert_eq!("/", Symbol::new("clojure.core//").name());
+    }
+
+    #[test]
+    fn keyword_keys() {
+        let mut m = BTreeMap::new();
+        m.insert(Keyword::new("user/id"), 5);
+        let v = vec![m.clone(), m];
+
+        let tr = ser::json::to_transit_json(&v);
+        assert_eq!(json!([["^ ", "~:user/id", 5], ["^ ", "^0", 5]]), tr);
+        let back: Vec<BTreeMap<Keyword, i32>> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(v, back);
+
+        let tr = ser::json_verbose::to_transit_json(&v);
+        assert_eq!(json!([{"~:user/id": 5}, {"~:user/id": 5}]), tr);
+        let back: Vec<BTreeMap<Keyword, i32>> = de::json_verbose::from_transit_json(tr).unwrap();
+        assert_eq!(v, back);
+    }
+
+    #[test]
+    fn symbols() {
+        let v = vec![Symbol::new("x"), Symbol::new("x")];
+        let tr = ser::json::to_transit_json(&v);
+        assert_eq!(json!(["~$x", "~$x"]), tr);
+        let tr = ser::json::to_transit_json(vec![Symbol::new("clojure.core/inc"); 2]);
+        assert_eq!(json!(["~$clojure.core/inc", "^0"]), tr);
+        let back: Vec<Symbol> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(vec![Symbol::new("clojure.core/inc"); 2], back);
+
+        let err: TResult<Symbol> = de::json::from_transit_json(json!(["~#'", "~:inc"]));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn keyword_handler() {
+        struct NameHandler;
+
+        impl handlers::WriteHandler<Keyword> for NameHandler {
+            fn tag(&self, _v: &Keyword) -> String {
+                "s".to_owned()
+            }
+
+            fn rep(&self, v: &Keyword) -> TransitValue {
+                TransitValue::String(v.name().to_owned())
+            }
+        }
+
+        let mut h = handlers::WriteHandlers::new();
+        h.insert(NameHandler);
+        let mut m = BTreeMap::new();
+        m.insert(Keyword::new("user/id"), Keyword::new("user/name"));
+        assert_eq!(
+            json!(["^ ", "~sid", "~sname"]),
+            ser::json::to_transit_json_with(&m, &h)
+        );
+    }
+
+    #[test]
+    fn uris() {
+        let uri = Uri::parse("https://example.com/a?b=c#d").unwrap();
+        let mut m = BTreeMap::new();
+        m.insert(uri.clone(), vec![uri]);
+        let tr = ser::json_verbose::to_transit_json(&m);
+        assert_eq!(
+            json!({"~rhttps://example.com/a?b=c#d": ["~rhttps://example.com/a?b=c#d"]}),
+            tr
+        );
+        let back: BTreeMap<Uri, Vec<Uri>> = de::json_verbose::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+
+        assert!(Uri::parse("").is_err());
+        let err: TResult<Uri> = de::json::from_transit_json(json!(["~#'", "~rhttp://a b"]));
+        match err {
+            Err(Error::CannotParse(_)) => (),
+            e => panic!("{:?} must be a parse error", e),
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let data = ByteBuf(vec![0, 1, 254, 255]);
+        let mut m = BTreeMap::new();
+        m.insert(data.clone(), data.clone());
+
+        let tr = ser::json::to_transit_json(&m);
+        assert_eq!(json!(["^ ", "~bAAH+/w==", "~bAAH+/w=="]), tr);
+        let back: BTreeMap<ByteBuf, ByteBuf> = de::json::from_transit_json(tr).unwrap();
+        assert_eq!(m, back);
+
+        let tr = ser::msgpack::to_transit_msgpack_value(vec![Bytes(&data)]);
+        assert_eq!(
+            rmpv::Value::Array(vec![rmpv::Value::Binary(data.to_vec())]),
+            tr
+        );
+        let mp = ser::msgpack::to_transit_msgpack(&m);
+        let back: BTreeMap<ByteBuf, ByteBuf> = de::msgpack::from_transit_msgpack(&mp).unwrap();
+        assert_eq!(m, back);
+        let back: TransitValue = de::msgpack::from_transit_msgpack(&mp).unwrap();
+        assert_eq!(
+            TransitValue::Map(vec![(
+                TransitValue::Bytes(data.to_vec()),
+                TransitValue::Bytes(data.to_vec())
+            )]),
+            back
+        );
+
+        let err: TResult<ByteBuf> = de::json::from_transit_json(json!(["~#'", "~b!!"]));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn bytes_handler() {
+        struct HexHandler;
+
+        impl handlers::WriteHandler<ByteBuf> for HexHandler {
+            fn tag(&self, _v: &ByteBuf) -> String {
+                "h".to_owned()
+            }
+
+            fn rep(&self, v: &ByteBuf) -> TransitValue {
+                TransitValue::String(v.iter().map(|b| format!("{:02x}", b)).collect())
+            }
+        }
+
+        let mut h = handlers::WriteHandlers::new();
+        h.insert(HexHandler);
+        let data = ByteBuf(vec![0, 255]);
+        let mut m = BTreeMap::new();
+        m.insert(Bytes(&data), data.clone());
+        assert_eq!(
+            json!(["^ ", "~h00ff", "~h00ff"]),
+            ser::json::to_transit_json_with(&m, &h)
+        );
+    }
+}
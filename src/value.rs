@@ -0,0 +1,667 @@
+//! Dynamic representation of Transit data, for documents whose shape is
+//! not known at compile time
+
+use crate::de::{Error, InputKind, TResult, TransitDeserialize, TransitDeserializer, TransitType};
+use crate::ser::{TransitKeySerializer, TransitSerialize, TransitSerializer};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, TimeZone, Utc};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Any Transit value. Maps keep entries in order of appearance and may have
+/// keys of any kind, so they are stored as a list of pairs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// Arbitrary precision integer in decimal notation
+    BigInt(String),
+    Float(f64),
+    /// Arbitrary precision decimal in decimal notation
+    BigDec(String),
+    String(String),
+    Keyword(String),
+    Symbol(String),
+    Char(char),
+    Bytes(Vec<u8>),
+    Uuid(u128),
+    Uri(String),
+    Instant(DateTime<Utc>),
+    Array(Vec<TransitValue>),
+    List(Vec<TransitValue>),
+    Set(Vec<TransitValue>),
+    Map(Vec<(TransitValue, TransitValue)>),
+    /// Value with a tag this library does not know, tag is stored without
+    /// `~#` or `~` prefix
+    Tagged(String, Box<TransitValue>),
+}
+
+fn uuid_to_string(u: u128) -> String {
+    let s = format!("{:032x}", u);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &s[..8],
+        &s[8..12],
+        &s[12..16],
+        &s[16..20],
+        &s[20..]
+    )
+}
+
+fn uuid_from_str(s: &str) -> Option<u128> {
+    let hyphens = s.len() == 36 && [8, 13, 18, 23].iter().all(|i| s.as_bytes()[*i] == b'-');
+    let digits: String = s.chars().filter(|c| *c != '-').collect();
+    if hyphens && digits.len() == 32 && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        u128::from_str_radix(&digits, 16).ok()
+    } else {
+        None
+    }
+}
+
+fn is_integer(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn not_match(s: &str, what: &str) -> Error {
+    Error::DoNotMatch(format!("{:?} is not {}", s, what))
+}
+
+impl TransitValue {
+    /// String representation of values which are written as tagged strings,
+    /// e.g. `~:keyword`
+    fn encoded(&self) -> Option<String> {
+        let s = match self {
+            TransitValue::BigInt(n) => format!("~n{}", n),
+            TransitValue::BigDec(d) => format!("~f{}", d),
+            TransitValue::Keyword(k) => format!("~:{}", k),
+            TransitValue::Symbol(s) => format!("~${}", s),
+            TransitValue::Char(c) => format!("~c{}", c),
+            TransitValue::Bytes(b) => format!("~b{}", BASE64.encode(b)),
+            TransitValue::Uuid(u) => format!("~u{}", uuid_to_string(*u)),
+            TransitValue::Uri(u) => format!("~r{}", u),
+            TransitValue::Tagged(tag, rep) if tag.chars().count() == 1 && tag != "#" => {
+                match rep.as_ref() {
+                    TransitValue::String(rep) => format!("~{}{}", tag, rep),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(s)
+    }
+
+    /// Reads a string which may hold any scalar value, as map keys do
+    fn decode(s: String) -> TResult<Self> {
+        let mut chars = s.chars();
+        let tag = match (chars.next(), chars.next()) {
+            (Some('~'), Some(tag)) => tag,
+            _ => return Ok(TransitValue::String(s)),
+        };
+        let rep = chars.as_str();
+        let v = match tag {
+            '~' | '^' | '`' => TransitValue::String(s[1..].to_owned()),
+            '_' => TransitValue::Null,
+            '?' => match rep {
+                "t" => TransitValue::Bool(true),
+                "f" => TransitValue::Bool(false),
+                _ => return Err(not_match(&s, "bool")),
+            },
+            'i' => TransitValue::Int(rep.parse().map_err(|_| not_match(&s, "int"))?),
+            'n' if is_integer(rep) => TransitValue::BigInt(rep.to_owned()),
+            'd' => TransitValue::Float(rep.parse().map_err(|_| not_match(&s, "float"))?),
+            'f' if rep.parse::<f64>().is_ok() => TransitValue::BigDec(rep.to_owned()),
+            'z' => match rep {
+                "NaN" => TransitValue::Float(f64::NAN),
+                "INF" => TransitValue::Float(f64::INFINITY),
+                "-INF" => TransitValue::Float(f64::NEG_INFINITY),
+                _ => return Err(not_match(&s, "special number")),
+            },
+            ':' => TransitValue::Keyword(rep.to_owned()),
+            '$' => TransitValue::Symbol(rep.to_owned()),
+            'c' => {
+                let mut chars = rep.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => TransitValue::Char(c),
+                    _ => return Err(not_match(&s, "char")),
+                }
+            }
+            'b' => TransitValue::Bytes(BASE64.decode(rep).map_err(|_| not_match(&s, "base64"))?),
+            'u' => TransitValue::Uuid(uuid_from_str(rep).ok_or_else(|| not_match(&s, "uuid"))?),
+            'r' => TransitValue::Uri(rep.to_owned()),
+            'm' => rep
+                .parse()
+                .ok()
+                .and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+                .map(TransitValue::Instant)
+                .ok_or_else(|| not_match(&s, "instant"))?,
+            't' => DateTime::parse_from_rfc3339(rep)
+                .map(|t| TransitValue::Instant(t.with_timezone(&Utc)))
+                .map_err(|_| not_match(&s, "instant"))?,
+            'n' | 'f' => return Err(not_match(&s, "number")),
+            tag => TransitValue::Tagged(
+                tag.to_string(),
+                Box::new(TransitValue::String(rep.to_owned())),
+            ),
+        };
+        Ok(v)
+    }
+
+    fn deserialize_all<'de, D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Vec<Self>> {
+        let (iter, _) = deserializer.clone().deserialize_array(input)?;
+        iter.map(|x| Self::transit_deserialize(deserializer.clone(), x))
+            .collect()
+    }
+
+    fn deserialize_tagged<'de, D: TransitDeserializer<'de>>(
+        deserializer: D,
+        tag: &str,
+        rep: D::Input,
+    ) -> TResult<Self> {
+        Self::from_tagged(tag, Self::transit_deserialize(deserializer, rep)?)
+    }
+
+    /// Values of tags known to this library, the others stay `Tagged`
+    fn from_tagged(tag: &str, rep: TransitValue) -> TResult<Self> {
+        match (tag, rep) {
+            ("" | "'", rep) => Ok(rep),
+            ("set", TransitValue::Array(v)) => Ok(TransitValue::Set(v)),
+            ("list", TransitValue::Array(v)) => Ok(TransitValue::List(v)),
+            ("cmap", TransitValue::Array(v)) => {
+                if v.len() % 2 == 1 {
+                    return Err(Error::DoNotMatch(
+                        "~#cmap must have even number of items".to_owned(),
+                    ));
+                }
+                let mut items = v.into_iter();
+                let mut pairs = Vec::with_capacity(items.len() / 2);
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    pairs.push((k, v));
+                }
+                Ok(TransitValue::Map(pairs))
+            }
+            // UUID as two 64-bit halves, used by some MessagePack writers
+            ("u", TransitValue::Array(v)) => match v.as_slice() {
+                [TransitValue::Int(hi), TransitValue::Int(lo)] => Ok(TransitValue::Uuid(
+                    u128::from(*hi as u64) << 64 | u128::from(*lo as u64),
+                )),
+                _ => Err(Error::DoNotMatch("~#u must be a pair of ints".to_owned())),
+            },
+            ("set" | "list" | "cmap" | "u", rep) => Err(Error::DoNotMatch(format!(
+                "~#{} must be an array, not {:?}",
+                tag, rep
+            ))),
+            (tag, rep) => Ok(TransitValue::Tagged(tag.to_owned(), Box::new(rep))),
+        }
+    }
+
+    fn deserialize_map<'de, D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        let (iter, len) = deserializer.clone().deserialize_map(input)?;
+        let mut pairs = Vec::with_capacity(len.unwrap_or(0));
+        for (k, v) in iter {
+            let k = deserializer.clone().deserialize_string(k)?;
+            if pairs.is_empty() && k.starts_with("~#") {
+                return Self::deserialize_tagged(deserializer, &k[2..], v);
+            }
+            pairs.push((
+                Self::decode(k)?,
+                Self::transit_deserialize(deserializer.clone(), v)?,
+            ));
+        }
+        Ok(TransitValue::Map(pairs))
+    }
+}
+
+impl TransitSerialize for TransitValue {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        match self {
+            TransitValue::Null => serializer.serialize_null(),
+            TransitValue::Bool(b) => b.transit_serialize(serializer),
+            TransitValue::Int(i) => i.transit_serialize(serializer),
+            TransitValue::Float(f) => f.transit_serialize(serializer),
+            TransitValue::String(s) => s.transit_serialize(serializer),
+            TransitValue::Instant(t) => t.transit_serialize(serializer),
+            TransitValue::Bytes(b) => serializer.serialize_bytes(b),
+            TransitValue::Array(v) => serializer.serialize_array_iter(v.iter()),
+            TransitValue::List(v) => serializer.serialize_tagged_array_iter("~#list", v.iter()),
+            TransitValue::Set(v) => serializer.serialize_tagged_array_iter("~#set", v.iter()),
+            TransitValue::Map(pairs) => {
+                serializer.serialize_map_iter(pairs.iter().map(|(k, v)| (k, v)))
+            }
+            v => match (v.encoded(), v) {
+                (Some(s), _) => serializer.serialize_string(&s),
+                (None, TransitValue::Tagged(tag, rep)) => {
+                    serializer.serialize_tagged(&format!("~#{}", tag), rep.as_ref())
+                }
+                _ => unreachable!("All scalars have string representation"),
+            },
+        }
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        match self {
+            TransitValue::Null => Some(serializer.serialize_key("~_")),
+            TransitValue::Bool(b) => b.transit_serialize_key(serializer),
+            TransitValue::Int(i) => i.transit_serialize_key(serializer),
+            TransitValue::Float(f) => f.transit_serialize_key(serializer),
+            TransitValue::String(s) => s.transit_serialize_key(serializer),
+            TransitValue::Instant(t) => t.transit_serialize_key(serializer),
+            v => v.encoded().map(|s| serializer.serialize_key(&s)),
+        }
+    }
+}
+
+/// Writes the value in EDN notation, `{:#}` pretty-prints it
+impl fmt::Display for TransitValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        crate::ser::edn::Edn(self).fmt(f)
+    }
+}
+
+/// May be a scalar or a composite value, maps of `TransitValue` read both
+/// kinds of keys on their own
+impl<'de> TransitDeserialize<'de> for TransitValue {
+    const TF_TYPE: TransitType = TransitType::Composite;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        match deserializer.input_kind(&input)? {
+            InputKind::Null => Ok(TransitValue::Null),
+            InputKind::Bool => deserializer.deserialize_bool(input).map(TransitValue::Bool),
+            InputKind::Int => deserializer.deserialize_int(input).map(TransitValue::Int),
+            InputKind::Float => deserializer
+                .deserialize_float(input)
+                .map(TransitValue::Float),
+            InputKind::String => Self::decode(deserializer.deserialize_string(input)?),
+            InputKind::Array => Self::deserialize_all(deserializer, input).map(TransitValue::Array),
+            InputKind::Map => Self::deserialize_map(deserializer, input),
+            InputKind::Bytes => deserializer
+                .deserialize_bytes(input)
+                .map(TransitValue::Bytes),
+        }
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::decode(deserializer.deserialize_string(input)?)
+    }
+}
+
+/// Representation of a `TaggedValue` as it was written. Strings keep their
+/// escapes and tags, e.g. `~t` instants keep precision and offset and `~i`
+/// ints stay strings, so forwarding does not change them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RawValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// String as written, e.g. `~t2019-04-01T12:30:00.123456+02:00`
+    String(String),
+    Bytes(Vec<u8>),
+    Array(Vec<RawValue>),
+    Map(Vec<(RawValue, RawValue)>),
+    /// Tag is stored without `~#` prefix
+    Tagged(String, Box<RawValue>),
+}
+
+impl RawValue {
+    /// Reads strings and tags the way `TransitValue` does
+    pub fn decode(self) -> TResult<TransitValue> {
+        Ok(match self {
+            RawValue::Null => TransitValue::Null,
+            RawValue::Bool(b) => TransitValue::Bool(b),
+            RawValue::Int(i) => TransitValue::Int(i),
+            RawValue::Float(f) => TransitValue::Float(f),
+            RawValue::String(s) => TransitValue::decode(s)?,
+            RawValue::Bytes(b) => TransitValue::Bytes(b),
+            RawValue::Array(v) => TransitValue::Array(
+                v.into_iter()
+                    .map(RawValue::decode)
+                    .collect::<TResult<_>>()?,
+            ),
+            RawValue::Map(pairs) => TransitValue::Map(
+                pairs
+                    .into_iter()
+                    .map(|(k, v)| Ok((k.decode()?, v.decode()?)))
+                    .collect::<TResult<_>>()?,
+            ),
+            RawValue::Tagged(tag, rep) => TransitValue::from_tagged(&tag, rep.decode()?)?,
+        })
+    }
+}
+
+impl TransitSerialize for RawValue {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        match self {
+            RawValue::Null => serializer.serialize_null(),
+            RawValue::Bool(b) => serializer.serialize_bool(*b),
+            RawValue::Int(i) => serializer.serialize_int(*i),
+            RawValue::Float(f) => serializer.serialize_float(*f),
+            RawValue::String(s) => serializer.serialize_string(s),
+            RawValue::Bytes(b) => serializer.serialize_bytes(b),
+            RawValue::Array(v) => serializer.serialize_array_iter(v.iter()),
+            RawValue::Map(pairs) => {
+                serializer.serialize_map_iter(pairs.iter().map(|(k, v)| (k, v)))
+            }
+            RawValue::Tagged(tag, rep) => {
+                serializer.serialize_tagged(&format!("~#{}", tag), rep.as_ref())
+            }
+        }
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        match self {
+            RawValue::Null => Some(serializer.serialize_key("~_")),
+            RawValue::Bool(b) => b.transit_serialize_key(serializer),
+            RawValue::Int(i) => i.transit_serialize_key(serializer),
+            RawValue::Float(f) => f.transit_serialize_key(serializer),
+            RawValue::String(s) => Some(serializer.serialize_key(s)),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> TransitDeserialize<'de> for RawValue {
+    const TF_TYPE: TransitType = TransitType::Composite;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        match deserializer.input_kind(&input)? {
+            InputKind::Null => Ok(RawValue::Null),
+            InputKind::Bool => deserializer.deserialize_bool(input).map(RawValue::Bool),
+            InputKind::Int => deserializer.deserialize_int(input).map(RawValue::Int),
+            InputKind::Float => deserializer.deserialize_float(input).map(RawValue::Float),
+            InputKind::String => deserializer.deserialize_string(input).map(RawValue::String),
+            InputKind::Bytes => deserializer.deserialize_bytes(input).map(RawValue::Bytes),
+            InputKind::Array => {
+                let (iter, _) = deserializer.clone().deserialize_array(input)?;
+                iter.map(|x| Self::transit_deserialize(deserializer.clone(), x))
+                    .collect::<TResult<_>>()
+                    .map(RawValue::Array)
+            }
+            InputKind::Map => {
+                let (iter, len) = deserializer.clone().deserialize_map(input)?;
+                let mut pairs = Vec::with_capacity(len.unwrap_or(0));
+                for (k, v) in iter {
+                    let k = deserializer.clone().deserialize_string(k)?;
+                    if pairs.is_empty() {
+                        if let Some(tag) = k.strip_prefix("~#") {
+                            let rep = Self::transit_deserialize(deserializer, v)?;
+                            return Ok(RawValue::Tagged(tag.to_owned(), Box::new(rep)));
+                        }
+                    }
+                    pairs.push((
+                        RawValue::String(k),
+                        Self::transit_deserialize(deserializer.clone(), v)?,
+                    ));
+                }
+                Ok(RawValue::Map(pairs))
+            }
+        }
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        deserializer.deserialize_string(input).map(RawValue::String)
+    }
+}
+
+/// Value with a tag the reader does not know, tag is stored without `~#`
+/// or `~` prefix. It is written back with the same tag and representation,
+/// so extensions survive forwarding by readers which do not understand them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaggedValue {
+    pub tag: String,
+    pub rep: RawValue,
+}
+
+impl TaggedValue {
+    fn is_scalar_tag(&self) -> bool {
+        self.tag.chars().count() == 1 && self.tag != "#"
+    }
+
+    /// Tags of one character with string representation are scalars
+    fn scalar(&self) -> Option<String> {
+        match &self.rep {
+            RawValue::String(rep) if self.is_scalar_tag() => Some(format!("~{}{}", self.tag, rep)),
+            _ => None,
+        }
+    }
+
+    /// Splits `"~xrep"` into its tag and representation
+    fn from_scalar(s: &str) -> TResult<Self> {
+        let not_tagged = || Error::DoNotMatch(format!("{:?} is not tagged", s));
+        let rest = s.strip_prefix('~').ok_or_else(not_tagged)?;
+        let tag = rest.chars().next().ok_or_else(not_tagged)?;
+        if let '~' | '^' | '`' | '#' = tag {
+            return Err(not_tagged());
+        }
+        let (tag, rep) = rest.split_at(tag.len_utf8());
+        Ok(TaggedValue {
+            tag: tag.to_owned(),
+            rep: RawValue::String(rep.to_owned()),
+        })
+    }
+
+    /// Representation read the way `TransitValue` reads it. Representation
+    /// of scalar tags is not encoded, so it stays a plain string.
+    pub fn decode_rep(self) -> TResult<TransitValue> {
+        match self.rep {
+            RawValue::String(s) if self.is_scalar_tag() => Ok(TransitValue::String(s)),
+            rep => rep.decode(),
+        }
+    }
+}
+
+impl TransitSerialize for TaggedValue {
+    fn transit_serialize<S: TransitSerializer>(&self, serializer: &S) -> S::Output {
+        match self.scalar() {
+            Some(s) => serializer.serialize_string(&s),
+            None => serializer.serialize_tagged(&format!("~#{}", self.tag), &self.rep),
+        }
+    }
+
+    fn transit_serialize_key<KS: TransitKeySerializer>(
+        &self,
+        serializer: &KS,
+    ) -> Option<KS::Output> {
+        self.scalar().map(|s| serializer.serialize_key(&s))
+    }
+}
+
+/// Reads both `"~xrep"` and `["~#tag", rep]`, whatever the tag is
+impl<'de> TransitDeserialize<'de> for TaggedValue {
+    const TF_TYPE: TransitType = TransitType::Composite;
+
+    fn transit_deserialize<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        match deserializer.input_kind(&input)? {
+            InputKind::String => Self::from_scalar(&deserializer.deserialize_string(input)?),
+            InputKind::Map => match RawValue::transit_deserialize(deserializer, input)? {
+                RawValue::Tagged(tag, rep) => Ok(TaggedValue { tag, rep: *rep }),
+                v => Err(Error::DoNotMatch(format!("{:?} is not tagged", v))),
+            },
+            kind => Err(Error::DoNotMatch(format!("{:?} is not tagged", kind))),
+        }
+    }
+
+    fn transit_deserialize_key<D: TransitDeserializer<'de>>(
+        deserializer: D,
+        input: D::Input,
+    ) -> TResult<Self> {
+        Self::from_scalar(&deserializer.deserialize_string(input)?)
+    }
+}
+
+/// Known tags are read into their own values, e.g. `~#set` into `Set`
+impl TryFrom<TaggedValue> for TransitValue {
+    type Error = Error;
+
+    fn try_from(v: TaggedValue) -> TResult<Self> {
+        let tag = v.tag.clone();
+        TransitValue::from_tagged(&tag, v.decode_rep()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::de::{json, json_verbose, msgpack, stream};
+    use crate::ser;
+    use serde_json::json;
+
+    fn sample() -> TransitValue {
+        use TransitValue::*;
+        Map(vec![
+            (Keyword("null".to_owned()), Null),
+            (String("~tricky".to_owned()), Bool(true)),
+            (Int(-4), Float(1.5)),
+            (
+                Keyword("scalars".to_owned()),
+                Array(vec![
+                    BigInt("123456789012345678901234567890".to_owned()),
+                    BigDec("1.25".to_owned()),
+                    Symbol("sym".to_owned()),
+                    Char('λ'),
+                    Bytes(vec![0, 1, 254, 255]),
+                    Uuid(0x5a2c_bb1f_d1f2_4c6e_8f5c_19ae_24c1_a7e0),
+                    Uri("https://example.com/?q=1".to_owned()),
+                    Instant(Utc.with_ymd_and_hms(2019, 4, 1, 12, 30, 0).unwrap()),
+                ]),
+            ),
+            (
+                Set(vec![Int(1)]),
+                List(vec![Keyword("a".to_owned()), Keyword("a".to_owned())]),
+            ),
+            (
+                Keyword("tagged".to_owned()),
+                Array(vec![
+                    Tagged("point".to_owned(), Box::new(Array(vec![Int(1), Int(2)]))),
+                    Tagged("x".to_owned(), Box::new(String("scalar".to_owned()))),
+                ]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn roundtrip() {
+        let v = sample();
+        assert_eq!(
+            v,
+            json::from_transit_json(ser::json::to_transit_json(&v)).unwrap()
+        );
+        assert_eq!(
+            v,
+            json_verbose::from_transit_json(ser::json_verbose::to_transit_json(&v)).unwrap()
+        );
+        assert_eq!(
+            v,
+            msgpack::from_transit_msgpack(&ser::msgpack::to_transit_msgpack(&v)).unwrap()
+        );
+        for mode in &[ser::stream::Mode::Json, ser::stream::Mode::JsonVerbose] {
+            let s = ser::stream::to_string(&v, *mode).unwrap();
+            assert_eq!(v, stream::from_str(&s).unwrap());
+        }
+    }
+
+    #[test]
+    fn scalars() {
+        let tr: TransitValue = json::from_transit_json(json!(["~#'", "~:kw"])).unwrap();
+        assert_eq!(TransitValue::Keyword("kw".to_owned()), tr);
+        let tr: TransitValue = stream::from_str(r#"["~#'", 1e3]"#).unwrap();
+        assert_eq!(TransitValue::Float(1000.0), tr);
+        let tr: TransitValue = json::from_transit_json(json!("~m1554121800000")).unwrap();
+        assert_eq!(
+            TransitValue::Instant(Utc.with_ymd_and_hms(2019, 4, 1, 12, 30, 0).unwrap()),
+            tr
+        );
+    }
+
+    #[test]
+    fn uuid_pair() {
+        let tr: TransitValue = json::from_transit_json(json!(["~#u", [1, -1]])).unwrap();
+        assert_eq!(TransitValue::Uuid(1 << 64 | u128::from(u64::MAX)), tr);
+        assert_eq!(
+            "00000000-0000-0001-ffff-ffffffffffff",
+            uuid_to_string(1 << 64 | u128::from(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn unknown_tags() {
+        let doc = json!([["~#point", [1, ["~#set", ["~:a"]]]], "~xred", "~~x"]);
+        let tr: Vec<TransitValue> = json::from_transit_json(doc.clone()).unwrap();
+        let tagged: TResult<Vec<TaggedValue>> = json::from_transit_json(doc.clone());
+        assert!(tagged.is_err());
+        let tagged: Vec<TaggedValue> = json::from_transit_json(json!([
+            ["~#point", [1, ["~#set", ["~:a"]]]],
+            "~xred",
+            ["~#set", [1]]
+        ]))
+        .unwrap();
+        assert_eq!(
+            TaggedValue {
+                tag: "x".to_owned(),
+                rep: RawValue::String("red".to_owned())
+            },
+            tagged[1]
+        );
+        assert_eq!(TransitValue::try_from(tagged[0].clone()).unwrap(), tr[0]);
+
+        let json_doc = ser::json::to_transit_json(&tagged);
+        assert_eq!(
+            json!([["~#point", [1, ["~#set", ["~:a"]]]], "~xred", ["^1", [1]]]),
+            json_doc
+        );
+        let verbose = ser::json_verbose::to_transit_json(&tagged);
+        assert_eq!(
+            json!([{"~#point": [1, {"~#set": ["~:a"]}]}, "~xred", {"~#set": [1]}]),
+            verbose
+        );
+        let tr: Vec<TaggedValue> = json_verbose::from_transit_json(verbose).unwrap();
+        assert_eq!(tagged, tr);
+        let mp = ser::msgpack::to_transit_msgpack(&tagged);
+        let tr: Vec<TaggedValue> = msgpack::from_transit_msgpack(&mp).unwrap();
+        assert_eq!(tagged, tr);
+    }
+
+    #[test]
+    fn forwarding_is_exact() {
+        let doc = r#"["~#unknown",["~t2019-04-01T12:30:00.123456+02:00","~i9007199254740993"]]"#;
+        let v: TaggedValue = stream::from_str(doc).unwrap();
+        assert_eq!(
+            doc,
+            ser::stream::to_string(&v, ser::stream::Mode::Json).unwrap()
+        );
+        let js: serde_json::Value = serde_json::from_str(doc).unwrap();
+        let v: TaggedValue = json::from_transit_json(js.clone()).unwrap();
+        assert_eq!(js, ser::json::to_transit_json(&v));
+        let v: TaggedValue =
+            msgpack::from_transit_msgpack(&ser::msgpack::to_transit_msgpack(&v)).unwrap();
+        assert_eq!(js, ser::json::to_transit_json(&v));
+    }
+}
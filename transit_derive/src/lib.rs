@@ -44,7 +44,7 @@ fn named_body(tag: String, fields: &syn::FieldsNamed) -> TokenStream {
 
 fn unnamed_body(tag: String, fields: &syn::FieldsUnnamed) -> TokenStream {
     let len = fields.unnamed.len();
-    let accessors = 0..len;
+    let accessors = (0..len).map(syn::Index::from);
     quote! {
         let mut ser_arr = serializer
             .clone()